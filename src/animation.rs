@@ -1,6 +1,7 @@
 use crate::{
-    build_piece, get_random_piece, move_piece_to_board, Active, AttemptingPlace, NextPieces,
-    PiecePlacedEvent, PieceType, PlacedPieces, RIGHT_GRID, SQUARE_SIZE,
+    cell_translation,
+    engine::{Input, BOARD_WIDTH, VISIBLE_HEIGHT},
+    placed_row, Active, AttemptingPlace, Engine, PiecePlacedEvent, PlacedPieces, Timing,
 };
 use bevy::prelude::*;
 #[derive(Resource)]
@@ -40,9 +41,11 @@ pub fn placing_piece_animation(
 #[derive(Resource)]
 pub struct FlashingAnimationTimer(pub Timer);
 
+#[allow(clippy::too_many_arguments)]
 pub fn place_piece_animation(
     time: Res<Time>,
-    query: Query<&Children>,
+    engine: Res<Engine>,
+    placed_pieces: Res<PlacedPieces>,
     mut child_query: Query<&mut Sprite>,
     mut original_color: Local<Color>,
     mut flashed: Local<Vec<Entity>>,
@@ -62,114 +65,98 @@ pub fn place_piece_animation(
         return;
     }
 
-    for ev in ev_place_piece.read() {
-        let children = if let Ok(children) = query.get(ev.0) {
-            children
+    for _ in ev_place_piece.read() {
+        let piece = if let Some(piece) = engine.last_locked {
+            piece
         } else {
             return;
         };
 
-        for child in children.iter() {
-            let mut sprite = child_query.get_mut(*child).unwrap();
+        // the piece is already on the board by the time this frame is drawn
+        for (x, y) in piece.cells() {
+            if y >= VISIBLE_HEIGHT as i32 {
+                continue;
+            }
+            let child = if let Some(child) = placed_pieces.0[placed_row(y)][x as usize] {
+                child
+            } else {
+                continue;
+            };
+            let mut sprite = child_query.get_mut(child).unwrap();
             *original_color = sprite.color;
             sprite.color.set_a(1.0);
             sprite.color.set_l(0.8);
-            flashed.push(*child);
+            flashed.push(child);
         }
     }
 }
 
-#[derive(Resource)]
-pub struct ClearingAnimationTimer(pub Timer);
-
+/// Removes cleared rows a column at a time over the line clear delay, then shifts the rows above
+/// down.
+#[allow(clippy::too_many_arguments)]
 pub fn clear_rows(
-    time: Res<Time>,
+    timing: Res<Timing>,
     mut commands: Commands,
+    mut engine: ResMut<Engine>,
     mut ev_clear: EventReader<crate::ClearEvent>,
     mut placed_pieces: ResMut<PlacedPieces>,
     mut clearing: Local<Vec<Vec<(usize, usize)>>>,
     mut rows_to_remove: Local<Vec<usize>>,
-    mut timer: ResMut<ClearingAnimationTimer>,
+    mut elapsed: Local<u32>,
     mut child_query: Query<&mut Transform, Without<Children>>,
-    mut next_pieces: ResMut<NextPieces>,
-    mut next_piece_query: Query<(&Children, &mut Transform, &PieceType), With<Children>>,
 ) {
     if !clearing.is_empty() {
-        if !timer.0.tick(time.delta()).just_finished() {
-            return;
-        }
-        match clearing.pop() {
-            Some(vec) => {
-                for (row, col) in vec {
-                    let entity = if let Some(entity) = placed_pieces.0[row][col] {
-                        entity
-                    } else {
-                        continue;
-                    };
+        *elapsed += 1;
+        let gone = *elapsed * BOARD_WIDTH as u32 / timing.line_clear.max(1);
+        while BOARD_WIDTH as u32 - (clearing.len() as u32) < gone {
+            let vec = if let Some(vec) = clearing.pop() {
+                vec
+            } else {
+                break;
+            };
+            for (row, col) in vec {
+                if let Some(entity) = placed_pieces.0[row][col].take() {
                     commands.entity(entity).despawn();
-                    //println!("despawning entity: {:?} at {row} {col}", entity);
-                    placed_pieces.0[row].remove(col);
-                    //println!("clearing: {:?}", clearing);
                 }
-                if clearing.is_empty() {
-                    // shift down
-                    let biggest_row = rows_to_remove.iter().max().unwrap_or(&0);
-                    let mut amount_to_shift = 1;
-                    for i in (0..*biggest_row).rev() {
-                        let row = &mut placed_pieces.0[i];
-                        if rows_to_remove.contains(&i) {
-                            amount_to_shift += 1;
-                            continue;
-                        }
-                        for entity in row.iter().filter(|&entity| entity.is_some()) {
-                            let mut transform = if let Ok(it) = child_query.get_mut(entity.unwrap())
-                            {
-                                it
-                            } else {
-                                continue;
-                            };
-                            transform.translation.y -= SQUARE_SIZE * amount_to_shift as f32;
-                        }
-                        placed_pieces.0[i + amount_to_shift] = row.clone();
-                        let row = &mut placed_pieces.0[i];
-                        for entity in row.iter_mut() {
-                            *entity = None;
-                        }
-                    }
-
-                    let next_piece = next_pieces.0.remove(0);
-                    let (children, mut transform, piece_type) =
-                        next_piece_query.get_mut(next_piece).unwrap();
-                    for child in children {
-                        commands.entity(*child).insert(Active);
-                    }
-                    commands.entity(next_piece).insert(Active);
-                    // if square or straight, position needs to move shifted up and to the right by SQUARE_SIZE
-                    // / 2.0
-                    move_piece_to_board(&piece_type, &mut transform.translation, &placed_pieces);
+            }
+        }
+        if !clearing.is_empty() {
+            return;
+        }
+        *elapsed = 0;
 
-                    let new_piece = get_random_piece();
-                    let entities = build_piece(
-                        &mut commands,
-                        &new_piece,
-                        Vec3::new(RIGHT_GRID * 1.5, 90.0, -1.0),
-                    );
-                    next_pieces.0.push(entities[0]);
+        // shift down
+        let height = placed_pieces.0.len();
+        let mut rows: Vec<Vec<Option<Entity>>> = placed_pieces
+            .0
+            .drain(..)
+            .enumerate()
+            .filter(|(i, _)| !rows_to_remove.contains(i))
+            .map(|(_, row)| row)
+            .collect();
+        while rows.len() < height {
+            rows.insert(0, vec![None; 10]);
+        }
+        for (i, row) in rows.iter().enumerate() {
+            for entity in row.iter().flatten() {
+                if let Ok(mut transform) = child_query.get_mut(*entity) {
+                    transform.translation.y = cell_translation(0, (height - 1 - i) as i32).y;
                 }
-                return;
             }
-
-            None => {}
         }
+        placed_pieces.0 = rows;
+
+        engine.step(Input::FinishClear);
+        return;
     }
     for ev in ev_clear.read() {
         for col in 0..10 {
             let mut indices: Vec<(usize, usize)> = vec![];
-            for row in &ev.0 {
+            for row in &ev.rows {
                 indices.push((*row, col));
             }
             clearing.push(indices);
         }
-        *rows_to_remove = ev.0.clone();
+        *rows_to_remove = ev.rows.clone();
     }
 }
@@ -0,0 +1,254 @@
+use bevy::prelude::*;
+
+use crate::{Action, GameState, KeyBindings, TitleMenu, NORMAL_BUTTON};
+
+/// Opens the controls menu from the title menu.
+#[derive(Component)]
+pub struct ControlsButton;
+
+#[derive(Component)]
+pub struct ControlsMenu;
+
+/// Waits for a key to bind to or unbind from its action.
+#[derive(Component)]
+pub struct BindingButton(pub Action);
+
+/// The keys bound to an action, shown on its [`BindingButton`].
+#[derive(Component)]
+pub struct BindingText(pub Action);
+
+#[derive(Component)]
+pub struct ResetBindingsButton;
+
+#[derive(Component)]
+pub struct ControlsBackButton;
+
+/// The action the next key pressed is bound to or unbound from, if one was picked.
+#[derive(Resource, Default)]
+pub struct Rebinding(pub Option<Action>);
+
+pub fn controls_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let root = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    align_self: AlignSelf::Stretch,
+                    justify_self: JustifySelf::Stretch,
+                    flex_wrap: FlexWrap::Wrap,
+                    justify_content: JustifyContent::FlexStart,
+                    align_items: AlignItems::FlexStart,
+                    align_content: AlignContent::FlexStart,
+                    height: Val::Percent(100.0),
+                    width: Val::Percent(100.0),
+                    ..Default::default()
+                },
+                background_color: BackgroundColor(Color::DARK_GRAY),
+                visibility: Visibility::Hidden,
+                ..Default::default()
+            },
+            ControlsMenu,
+        ))
+        .id();
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text_style = |font_size: f32| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    let menu = commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Controls", text_style(60.0)));
+            parent.spawn(TextBundle::from_section(
+                "Click an action, then press a key to add it or one of its keys to remove it",
+                text_style(20.0),
+            ));
+            for action in Action::ALL {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            column_gap: Val::Px(20.0),
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle {
+                            text: Text::from_section(action.label(), text_style(24.0)),
+                            style: Style {
+                                width: Val::Px(160.0),
+                                ..default()
+                            },
+                            ..default()
+                        });
+                        menu_button(
+                            parent,
+                            320.0,
+                            (
+                                TextBundle::from_section("", text_style(24.0)),
+                                BindingText(action),
+                            ),
+                            BindingButton(action),
+                        );
+                    });
+            }
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        margin: UiRect::top(Val::Px(12.0)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    let reset = TextBundle::from_section("Reset", text_style(24.0));
+                    menu_button(parent, 150.0, reset, ResetBindingsButton);
+                    let back = TextBundle::from_section("Back", text_style(24.0));
+                    menu_button(parent, 150.0, back, ControlsBackButton);
+                });
+        })
+        .id();
+
+    commands.entity(root).add_child(menu);
+}
+
+fn menu_button(parent: &mut ChildBuilder, width: f32, text: impl Bundle, button: impl Component) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(width),
+                    height: Val::Px(32.0),
+                    border: UiRect::all(Val::Px(3.0)),
+                    // horizontally center child text
+                    justify_content: JustifyContent::Center,
+                    // vertically center child text
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_color: BorderColor(Color::BLACK),
+                background_color: NORMAL_BUTTON.into(),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(text);
+        });
+}
+
+pub fn controls_button(
+    controls_button: Query<&Interaction, (Changed<Interaction>, With<ControlsButton>)>,
+    mut query: Query<&mut Visibility, With<TitleMenu>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for interaction in &mut controls_button.iter() {
+        if *interaction == Interaction::Pressed {
+            *query.single_mut() = Visibility::Hidden;
+            next_state.set(GameState::Controls);
+        }
+    }
+}
+
+pub fn enter_controls(mut controls_menu: Query<&mut Visibility, With<ControlsMenu>>) {
+    *controls_menu.single_mut() = Visibility::Visible;
+}
+
+pub fn exit_controls(
+    mut rebinding: ResMut<Rebinding>,
+    mut controls_menu: Query<&mut Visibility, With<ControlsMenu>>,
+) {
+    rebinding.0 = None;
+    *controls_menu.single_mut() = Visibility::Hidden;
+}
+
+/// Picks the action to rebind, resets every binding or goes back to the title menu.
+pub fn controls_menu_buttons(
+    binding_buttons: Query<(&Interaction, &BindingButton), Changed<Interaction>>,
+    reset_button: Query<&Interaction, (Changed<Interaction>, With<ResetBindingsButton>)>,
+    back_button: Query<&Interaction, (Changed<Interaction>, With<ControlsBackButton>)>,
+    mut bindings: ResMut<KeyBindings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut title_menu: Query<&mut Visibility, With<TitleMenu>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, button) in binding_buttons.iter() {
+        if *interaction == Interaction::Pressed {
+            rebinding.0 = Some(button.0);
+        }
+    }
+    if reset_button.iter().any(|&i| i == Interaction::Pressed) {
+        *bindings = KeyBindings::default();
+        bindings.save();
+    }
+    if back_button.iter().any(|&i| i == Interaction::Pressed) {
+        *title_menu.single_mut() = Visibility::Visible;
+        next_state.set(GameState::Title);
+    }
+}
+
+/// Binds or unbinds the first key pressed after an action was picked, and saves the bindings.
+pub fn rebind_key(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut bindings: ResMut<KeyBindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    let action = if let Some(action) = rebinding.0 {
+        action
+    } else {
+        return;
+    };
+    if let Some(&key) = keyboard_input.get_just_pressed().next() {
+        bindings.toggle(action, key);
+        bindings.save();
+        rebinding.0 = None;
+    }
+}
+
+pub fn update_binding_text(
+    bindings: Res<KeyBindings>,
+    rebinding: Res<Rebinding>,
+    mut query: Query<(&mut Text, &BindingText)>,
+) {
+    if !bindings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+
+    for (mut text, binding) in query.iter_mut() {
+        text.sections[0].value = if rebinding.0 == Some(binding.0) {
+            "press a key...".to_string()
+        } else if bindings.keys(binding.0).is_empty() {
+            "-".to_string()
+        } else {
+            bindings
+                .keys(binding.0)
+                .iter()
+                .map(key_name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+    }
+}
+
+/// How a key is shown in the controls menu: `KeyZ` as `Z` and `Digit1` as `1`, others by name.
+fn key_name(key: &KeyCode) -> String {
+    let name = format!("{:?}", key);
+    name.strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(&name)
+        .to_string()
+}
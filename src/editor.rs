@@ -0,0 +1,284 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    build_board, build_held_piece, build_piece, cell_translation,
+    engine::{Cell, PieceKind, RotationSystem},
+    placed_row, translation_cell, DespawnOnRestart, Fumen, GameMode, GameState, Hold, NextPieces,
+    PiecePartBundle, Placed, PlacedPieces, RestartGameEvent, Settings, TitleMenu,
+};
+
+/// What the number keys 1 to 8 paint with.
+const PAINTS: [Cell; 8] = [
+    Cell::Piece(PieceKind::Straight),
+    Cell::Piece(PieceKind::Square),
+    Cell::Piece(PieceKind::T),
+    Cell::Piece(PieceKind::Z),
+    Cell::Piece(PieceKind::ReverseZ),
+    Cell::Piece(PieceKind::ReverseL),
+    Cell::Piece(PieceKind::L),
+    Cell::Garbage,
+];
+
+const PAINT_KEYS: [KeyCode; 8] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+];
+
+/// The position being set up in [`GameState::Editor`], started from with an endless game.
+#[derive(Resource)]
+pub struct Editor {
+    pub setup: Fumen,
+    pub paint: Cell,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Editor {
+            setup: Fumen::default(),
+            paint: Cell::Garbage,
+        }
+    }
+}
+
+/// Opens the editor from the title menu.
+#[derive(Component)]
+pub struct EditorButton;
+
+/// What is being painted, the hold and queue, and the keys of the editor.
+#[derive(Component)]
+pub struct EditorText;
+
+pub fn editor_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(20.0),
+                bottom: Val::Px(20.0),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        EditorText,
+    ));
+}
+
+pub fn editor_button(
+    editor_button: Query<&Interaction, (Changed<Interaction>, With<EditorButton>)>,
+    mut query: Query<&mut Visibility, With<TitleMenu>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for interaction in &mut editor_button.iter() {
+        if *interaction == Interaction::Pressed {
+            *query.single_mut() = Visibility::Hidden;
+            next_state.set(GameState::Editor);
+        }
+    }
+}
+
+/// Clears away the game set up behind the title menu and shows the setup last played from, if any.
+pub fn enter_editor(
+    mut commands: Commands,
+    entities_to_despawn: Query<Entity, With<DespawnOnRestart>>,
+    mut settings: ResMut<Settings>,
+    mut editor: ResMut<Editor>,
+    mut placed_pieces: ResMut<PlacedPieces>,
+    mut next_pieces: ResMut<NextPieces>,
+    mut editor_text: Query<&mut Visibility, With<EditorText>>,
+) {
+    for entity in entities_to_despawn.iter() {
+        commands.entity(entity).despawn();
+    }
+    for row in placed_pieces.0.iter_mut() {
+        for entity in row.iter_mut() {
+            *entity = None;
+        }
+    }
+    next_pieces.0.clear();
+
+    settings.ruleset.mode = GameMode::Endless;
+    settings.puzzle = None;
+    let setup = settings.fumen.clone().unwrap_or_default();
+    editor.setup = Fumen {
+        queue: setup.pieces(),
+        active: None,
+        ..setup
+    };
+    build_board(&mut commands, &editor.setup.board, &mut placed_pieces);
+    let system = settings.ruleset.rotation;
+    if let Some(hold) = editor.setup.hold {
+        build_held_piece(&mut commands, hold, system);
+    }
+    for &kind in &editor.setup.queue {
+        next_pieces
+            .0
+            .push(build_next_piece(&mut commands, kind, system));
+    }
+
+    *editor_text.single_mut() = Visibility::Visible;
+}
+
+pub fn exit_editor(mut editor_text: Query<&mut Visibility, With<EditorText>>) {
+    *editor_text.single_mut() = Visibility::Hidden;
+}
+
+/// Paints the cell under the mouse while the left button is held, and erases it with the right.
+pub fn paint_cells(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    mut placed_pieces: ResMut<PlacedPieces>,
+) {
+    let cell = if mouse_input.pressed(MouseButton::Left) {
+        Some(editor.paint)
+    } else if mouse_input.pressed(MouseButton::Right) {
+        None
+    } else {
+        return;
+    };
+    let (camera, camera_transform) = camera.single();
+    let (x, y) = match windows
+        .single()
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+        .and_then(translation_cell)
+    {
+        Some(position) => position,
+        None => return,
+    };
+    if editor.setup.board.get(x, y) == cell {
+        return;
+    }
+
+    editor.setup.board.set(x, y, cell);
+    let placed = &mut placed_pieces.0[placed_row(y)][x as usize];
+    if let Some(entity) = placed.take() {
+        commands.entity(entity).despawn();
+    }
+    if let Some(cell) = cell {
+        let translation = cell_translation(x, y);
+        *placed = Some(
+            commands
+                .spawn((PiecePartBundle::new(&cell.into(), &translation), Placed))
+                .id(),
+        );
+    }
+}
+
+/// Picks what to paint, sets the hold and queue, and starts playing from the setup or leaves it.
+#[allow(clippy::too_many_arguments)]
+pub fn edit_setup(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    held_query: Query<Entity, With<Hold>>,
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    mut settings: ResMut<Settings>,
+    mut placed_pieces: ResMut<PlacedPieces>,
+    mut next_pieces: ResMut<NextPieces>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut title_menu: Query<&mut Visibility, With<TitleMenu>>,
+    mut ev_restart: EventWriter<RestartGameEvent>,
+) {
+    for (&key, &paint) in PAINT_KEYS.iter().zip(&PAINTS) {
+        if keyboard_input.just_pressed(key) {
+            editor.paint = paint;
+        }
+    }
+    let piece = match editor.paint {
+        Cell::Piece(kind) => Some(kind),
+        Cell::Garbage => None,
+    };
+
+    if keyboard_input.just_pressed(KeyCode::KeyH) {
+        for entity in held_query.iter() {
+            commands.entity(entity).despawn();
+        }
+        // painting garbage empties the hold
+        editor.setup.hold = piece;
+        if let Some(kind) = piece {
+            build_held_piece(&mut commands, kind, settings.ruleset.rotation);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyN) {
+        if let Some(kind) = piece {
+            editor.setup.queue.push(kind);
+            next_pieces.0.push(build_next_piece(
+                &mut commands,
+                kind,
+                settings.ruleset.rotation,
+            ));
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        editor.setup.queue.pop();
+        if let Some(entity) = next_pieces.0.pop() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyC) {
+        editor.setup.board = default();
+        for entity in placed_pieces
+            .0
+            .iter_mut()
+            .flatten()
+            .filter_map(Option::take)
+        {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        settings.fumen = Some(editor.setup.clone());
+        ev_restart.send(RestartGameEvent);
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        *title_menu.single_mut() = Visibility::Visible;
+        next_state.set(GameState::Title);
+    }
+}
+
+pub fn update_editor_text(
+    editor: Res<Editor>,
+    mut editor_text: Query<&mut Text, With<EditorText>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+
+    let paint = match editor.paint {
+        Cell::Piece(kind) => kind.letter().to_string(),
+        Cell::Garbage => "garbage".to_string(),
+    };
+    let hold = editor
+        .setup
+        .hold
+        .map_or("-".to_string(), |kind| kind.letter().into());
+    let queue: String = editor.setup.queue.iter().map(PieceKind::letter).collect();
+    editor_text.single_mut().sections[0].value = format!(
+        "PAINT {}   HOLD {}   NEXT {}\n\
+         1-8 paint IOTSZJL/garbage, left click paint, right click erase\n\
+         H hold, N queue, Backspace unqueue, C clear, E export, Enter play, Esc leave",
+        paint, hold, queue
+    );
+}
+
+fn build_next_piece(commands: &mut Commands, kind: PieceKind, system: RotationSystem) -> Entity {
+    build_piece(commands, &kind.into(), system, Vec3::new(0.0, 0.0, -1.0))[0]
+}
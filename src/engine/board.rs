@@ -0,0 +1,135 @@
+use crate::engine::{Piece, PieceKind};
+
+pub const BOARD_WIDTH: usize = 10;
+/// Rows shown on screen. A piece locking above this line ends the game.
+pub const VISIBLE_HEIGHT: usize = 20;
+/// Hidden rows above the visible field so pieces can spawn and rotate above a tall stack.
+pub const BOARD_HEIGHT: usize = VISIBLE_HEIGHT + 4;
+
+/// What fills an occupied cell of the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cell {
+    Piece(PieceKind),
+    /// Rows pushed in from below rather than locked by the player.
+    Garbage,
+}
+
+impl From<PieceKind> for Cell {
+    fn from(kind: PieceKind) -> Self {
+        Cell::Piece(kind)
+    }
+}
+
+/// The playfield. Row 0 is the bottom row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Board {
+    rows: Vec<[Option<Cell>; BOARD_WIDTH]>,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Board {
+            rows: vec![[None; BOARD_WIDTH]; BOARD_HEIGHT],
+        }
+    }
+}
+
+impl Board {
+    pub fn get(&self, x: i32, y: i32) -> Option<Cell> {
+        if !Board::in_bounds(x, y) {
+            return None;
+        }
+        self.rows[y as usize][x as usize]
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, cell: Option<Cell>) {
+        if Board::in_bounds(x, y) {
+            self.rows[y as usize][x as usize] = cell;
+        }
+    }
+
+    /// Walls and floor count as occupied, the space above the board does not.
+    pub fn is_occupied(&self, x: i32, y: i32) -> bool {
+        if x < 0 || x >= BOARD_WIDTH as i32 || y < 0 {
+            return true;
+        }
+        self.get(x, y).is_some()
+    }
+
+    pub fn fits(&self, piece: &Piece) -> bool {
+        piece.cells().iter().all(|&(x, y)| !self.is_occupied(x, y))
+    }
+
+    pub fn full_rows(&self) -> Vec<usize> {
+        self.rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.iter().all(|cell| cell.is_some()))
+            .map(|(y, _)| y)
+            .collect()
+    }
+
+    /// Removes the given rows and drops everything above them.
+    pub fn clear_rows(&mut self, rows: &[usize]) {
+        let mut kept: Vec<[Option<Cell>; BOARD_WIDTH]> = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(y, _)| !rows.contains(y))
+            .map(|(_, row)| *row)
+            .collect();
+        kept.resize(BOARD_HEIGHT, [None; BOARD_WIDTH]);
+        self.rows = kept;
+    }
+
+    /// Pushes a row of garbage in at the bottom with a hole at `hole`, raising everything above
+    /// it. Whatever is pushed past the top of the board is lost.
+    pub fn push_garbage(&mut self, hole: usize) {
+        let mut row = [Some(Cell::Garbage); BOARD_WIDTH];
+        row[hole] = None;
+        self.rows.insert(0, row);
+        self.rows.truncate(BOARD_HEIGHT);
+    }
+
+    /// Whether row `y` has at least one garbage cell left in it.
+    pub fn has_garbage(&self, y: usize) -> bool {
+        self.rows[y].contains(&Some(Cell::Garbage))
+    }
+
+    /// Rows with at least one garbage cell left in them.
+    pub fn garbage_rows(&self) -> usize {
+        (0..BOARD_HEIGHT).filter(|&y| self.has_garbage(y)).count()
+    }
+
+    /// Whether anything is in the hidden rows above the visible field.
+    pub fn is_above_visible(&self) -> bool {
+        self.rows[VISIBLE_HEIGHT..]
+            .iter()
+            .flatten()
+            .any(|cell| cell.is_some())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().flatten().all(|cell| cell.is_none())
+    }
+
+    fn in_bounds(x: i32, y: i32) -> bool {
+        x >= 0 && x < BOARD_WIDTH as i32 && y >= 0 && y < BOARD_HEIGHT as i32
+    }
+}
+
+#[cfg(test)]
+impl Board {
+    /// A board from rows drawn top to bottom, `#` for a filled cell, the last row being the floor.
+    pub fn drawn(rows: &[&str]) -> Board {
+        let mut board = Board::default();
+        for (y, row) in rows.iter().rev().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                if cell == '#' {
+                    board.set(x as i32, y as i32, Some(Cell::Garbage));
+                }
+            }
+        }
+        board
+    }
+}
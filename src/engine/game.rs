@@ -0,0 +1,419 @@
+use std::collections::VecDeque;
+
+use crate::engine::{
+    centre_column_blocked, get_180_kick_values, get_kick_values, t_spin, Board, Kick, Kicks180,
+    Piece, PieceKind, RotationSystem, Spin, BOARD_HEIGHT, VISIBLE_HEIGHT,
+};
+
+/// A single player action or rules tick applied to the game with [`Game::step`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    /// Turn the active piece half around.
+    Rotate180,
+    Hold,
+    /// Move the active piece down one row because of gravity.
+    Gravity,
+    /// Lock the active piece where it is.
+    Lock,
+    /// Remove the rows cleared by the last lock.
+    FinishClear,
+    /// Bring in the next piece once the last one has locked and its rows are gone. Leaving time
+    /// before this is the entry delay.
+    Spawn,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GameEvent {
+    /// A piece was pulled from the next queue and is now active.
+    Spawned(PieceKind),
+    Moved,
+    Rotated {
+        kick: usize,
+    },
+    Fell,
+    SoftDropped,
+    HardDropped {
+        rows: u32,
+    },
+    Locked(Piece),
+    /// Full rows waiting for [`Input::FinishClear`].
+    LinesCleared(LineClear),
+    /// The active piece went into hold. `swapped` is the piece that came out of hold, if any.
+    Held {
+        swapped: Option<PieceKind>,
+    },
+    GameOver,
+}
+
+/// A lock that filled rows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineClear {
+    /// Full rows, counted from the bottom.
+    pub rows: Vec<usize>,
+    pub spin: Option<Spin>,
+    /// This and the previous clear were both difficult: a tetris or a T-spin.
+    pub back_to_back: bool,
+    /// Clears in a row before this one, 0 for the first.
+    pub combo: u32,
+    /// Removing the rows leaves the board empty.
+    pub perfect: bool,
+    /// Rows among `rows` with garbage in them.
+    pub garbage: usize,
+}
+
+impl LineClear {
+    pub fn is_difficult(&self) -> bool {
+        self.rows.len() == 4 || self.spin.is_some()
+    }
+}
+
+/// Board, active piece, hold and next queue, without any notion of time or rendering.
+/// Callers feed new pieces in with [`Game::push_next`] and bring each one in with [`Input::Spawn`]
+/// after the last one locks.
+#[derive(Clone, Debug)]
+pub struct Game {
+    pub board: Board,
+    pub active: Option<Piece>,
+    pub hold: Option<PieceKind>,
+    pub can_hold: bool,
+    pub next: VecDeque<PieceKind>,
+    pub last_locked: Option<Piece>,
+    /// T-spin made by the last locked piece.
+    pub last_spin: Option<Spin>,
+    /// Rows filled by the last locked piece.
+    pub last_clear: Option<LineClear>,
+    /// The last clear was difficult, so the next difficult one is back-to-back.
+    pub back_to_back: bool,
+    /// Clears in a row so far, `None` once a piece locks without clearing.
+    pub combo: Option<u32>,
+    /// Shapes and kicks of the pieces spawned from now on.
+    pub rotation_system: RotationSystem,
+    pub kicks_180: Kicks180,
+    /// Kick used by the active piece's last move, if that move was a rotation.
+    last_kick: Option<Kick>,
+    clearing: Vec<usize>,
+    game_over: bool,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game {
+            board: Board::default(),
+            active: None,
+            hold: None,
+            can_hold: true,
+            next: VecDeque::new(),
+            last_locked: None,
+            last_spin: None,
+            last_clear: None,
+            back_to_back: false,
+            combo: None,
+            rotation_system: RotationSystem::default(),
+            kicks_180: Kicks180::default(),
+            last_kick: None,
+            clearing: Vec::new(),
+            game_over: false,
+        }
+    }
+}
+
+impl Game {
+    pub fn push_next(&mut self, piece_kind: PieceKind) {
+        self.next.push_back(piece_kind);
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.game_over
+    }
+
+    /// Rows cleared by the last lock that have not been removed yet.
+    pub fn clearing_rows(&self) -> &[usize] {
+        &self.clearing
+    }
+
+    /// True if the active piece is resting on the floor or the stack.
+    pub fn is_grounded(&self) -> bool {
+        match self.active {
+            Some(piece) => !self.board.fits(&piece.shifted(0, -1)),
+            None => false,
+        }
+    }
+
+    pub fn can_move(&self, dx: i32) -> bool {
+        match self.active {
+            Some(piece) => self.board.fits(&piece.shifted(dx, 0)),
+            None => false,
+        }
+    }
+
+    /// Where the active piece would land if hard dropped.
+    pub fn ghost(&self) -> Option<Piece> {
+        let mut ghost = self.active?;
+        while self.board.fits(&ghost.shifted(0, -1)) {
+            ghost = ghost.shifted(0, -1);
+        }
+        Some(ghost)
+    }
+
+    pub fn step(&mut self, input: Input) -> Vec<GameEvent> {
+        if self.game_over {
+            return vec![];
+        }
+
+        match input {
+            Input::MoveLeft => self.shift(-1, 0, GameEvent::Moved),
+            Input::MoveRight => self.shift(1, 0, GameEvent::Moved),
+            Input::SoftDrop => self.shift(0, -1, GameEvent::SoftDropped),
+            Input::Gravity => self.shift(0, -1, GameEvent::Fell),
+            Input::HardDrop => self.hard_drop(),
+            Input::RotateClockwise => self.rotate(true),
+            Input::RotateCounterClockwise => self.rotate(false),
+            Input::Rotate180 => self.rotate_180(),
+            Input::Hold => self.hold(),
+            Input::Lock => self.lock(),
+            Input::FinishClear => self.finish_clear(),
+            Input::Spawn => {
+                if self.active.is_some() || !self.clearing.is_empty() {
+                    return vec![];
+                }
+                self.spawn_next()
+            }
+        }
+    }
+
+    /// Makes the first piece of the next queue active.
+    pub fn spawn_next(&mut self) -> Vec<GameEvent> {
+        let piece_kind = if let Some(piece_kind) = self.next.pop_front() {
+            piece_kind
+        } else {
+            return vec![];
+        };
+
+        let mut events = vec![GameEvent::Spawned(piece_kind)];
+        events.extend(self.spawn(piece_kind));
+        events
+    }
+
+    /// Pushes a row of garbage in at the bottom for each of `holes`. The active piece is pushed up
+    /// with the stack if it would overlap. Pushing the stack out of the visible field, or the
+    /// active piece out of the top of the board, ends the game.
+    pub fn push_garbage(&mut self, holes: &[usize]) -> Vec<GameEvent> {
+        if self.game_over {
+            return vec![];
+        }
+
+        for &hole in holes {
+            self.board.push_garbage(hole);
+        }
+        if let Some(mut piece) = self.active {
+            while !self.board.fits(&piece) {
+                piece = piece.shifted(0, 1);
+            }
+            self.active = Some(piece);
+            if piece.cells().iter().any(|&(_, y)| y >= BOARD_HEIGHT as i32) {
+                self.game_over = true;
+                return vec![GameEvent::GameOver];
+            }
+        }
+
+        if self.board.is_above_visible() {
+            self.game_over = true;
+            return vec![GameEvent::GameOver];
+        }
+        vec![]
+    }
+
+    fn spawn(&mut self, piece_kind: PieceKind) -> Vec<GameEvent> {
+        let mut piece = Piece::with_system(piece_kind, self.rotation_system);
+
+        // move the piece up to avoid collision with placed pieces
+        for _ in 0..2 {
+            if self.board.fits(&piece) {
+                break;
+            }
+            piece = piece.shifted(0, 1);
+        }
+
+        self.active = Some(piece);
+        self.last_kick = None;
+        if !self.board.fits(&piece) {
+            self.game_over = true;
+            return vec![GameEvent::GameOver];
+        }
+
+        vec![]
+    }
+
+    fn shift(&mut self, dx: i32, dy: i32, event: GameEvent) -> Vec<GameEvent> {
+        let piece = if let Some(piece) = self.active {
+            piece.shifted(dx, dy)
+        } else {
+            return vec![];
+        };
+
+        if !self.board.fits(&piece) {
+            return vec![];
+        }
+
+        self.active = Some(piece);
+        self.last_kick = None;
+        vec![event]
+    }
+
+    fn rotate(&mut self, is_clockwise: bool) -> Vec<GameEvent> {
+        let piece = if let Some(piece) = self.active {
+            piece
+        } else {
+            return vec![];
+        };
+
+        let rotated = piece.rotated(is_clockwise);
+        let mut kick_values =
+            get_kick_values(piece.system, piece.kind, piece.rotation, rotated.rotation);
+        if piece.system == RotationSystem::Ars && centre_column_blocked(&self.board, &rotated) {
+            kick_values = &kick_values[..1];
+        }
+        self.kick(rotated, kick_values, false)
+    }
+
+    fn rotate_180(&mut self) -> Vec<GameEvent> {
+        let piece = if let Some(piece) = self.active {
+            piece
+        } else {
+            return vec![];
+        };
+
+        let kick_values =
+            get_180_kick_values(self.kicks_180, piece.system, piece.kind, piece.rotation);
+        self.kick(piece.flipped(), kick_values, true)
+    }
+
+    /// Puts `rotated` in place of the active piece at the first of `kick_values` it fits at.
+    fn kick(
+        &mut self,
+        rotated: Piece,
+        kick_values: &[(i32, i32)],
+        half_turn: bool,
+    ) -> Vec<GameEvent> {
+        for (kick, &(dx, dy)) in kick_values.iter().enumerate() {
+            let kicked = rotated.shifted(dx, dy);
+            if self.board.fits(&kicked) {
+                self.active = Some(kicked);
+                self.last_kick = Some(Kick {
+                    index: kick,
+                    half_turn,
+                });
+                return vec![GameEvent::Rotated { kick }];
+            }
+        }
+
+        vec![]
+    }
+
+    fn hard_drop(&mut self) -> Vec<GameEvent> {
+        let (piece, ghost) = match (self.active, self.ghost()) {
+            (Some(piece), Some(ghost)) => (piece, ghost),
+            _ => return vec![],
+        };
+
+        self.active = Some(ghost);
+        if ghost.y != piece.y {
+            self.last_kick = None;
+        }
+        let mut events = vec![GameEvent::HardDropped {
+            rows: (piece.y - ghost.y) as u32,
+        }];
+        events.extend(self.lock());
+        events
+    }
+
+    fn lock(&mut self) -> Vec<GameEvent> {
+        let piece = if let Some(piece) = self.active.take() {
+            piece
+        } else {
+            return vec![];
+        };
+
+        self.last_spin = t_spin(&self.board, &piece, self.last_kick);
+        for (x, y) in piece.cells() {
+            self.board.set(x, y, Some(piece.kind.into()));
+        }
+        self.last_locked = Some(piece);
+        self.can_hold = true;
+
+        let mut events = vec![GameEvent::Locked(piece)];
+        if piece
+            .cells()
+            .iter()
+            .any(|&(_, y)| y >= VISIBLE_HEIGHT as i32)
+        {
+            self.game_over = true;
+            events.push(GameEvent::GameOver);
+            return events;
+        }
+
+        let full_rows = self.board.full_rows();
+        if full_rows.is_empty() {
+            self.last_clear = None;
+            self.combo = None;
+        } else {
+            let combo = self.combo.map_or(0, |combo| combo + 1);
+            let mut cleared = self.board.clone();
+            cleared.clear_rows(&full_rows);
+            let mut clear = LineClear {
+                rows: full_rows.clone(),
+                spin: self.last_spin,
+                back_to_back: false,
+                combo,
+                perfect: cleared.is_empty(),
+                garbage: full_rows
+                    .iter()
+                    .filter(|&&y| self.board.has_garbage(y))
+                    .count(),
+            };
+            clear.back_to_back = self.back_to_back && clear.is_difficult();
+            self.back_to_back = clear.is_difficult();
+            self.combo = Some(combo);
+            self.last_clear = Some(clear.clone());
+
+            self.clearing = full_rows;
+            events.push(GameEvent::LinesCleared(clear));
+        }
+
+        events
+    }
+
+    fn finish_clear(&mut self) -> Vec<GameEvent> {
+        let rows = std::mem::take(&mut self.clearing);
+        self.board.clear_rows(&rows);
+        vec![]
+    }
+
+    fn hold(&mut self) -> Vec<GameEvent> {
+        // with nothing to swap in or to come next, holding would leave no piece to play
+        if !self.can_hold || (self.hold.is_none() && self.next.is_empty()) {
+            return vec![];
+        }
+        let piece = if let Some(piece) = self.active.take() {
+            piece
+        } else {
+            return vec![];
+        };
+
+        let swapped = self.hold.replace(piece.kind);
+        let mut events = vec![GameEvent::Held { swapped }];
+        match swapped {
+            Some(piece_kind) => events.extend(self.spawn(piece_kind)),
+            None => events.extend(self.spawn_next()),
+        }
+        self.can_hold = false;
+
+        events
+    }
+}
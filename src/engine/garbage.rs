@@ -0,0 +1,56 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::engine::BOARD_WIDTH;
+
+/// Picks the hole of each garbage row. A row keeps the hole of the one below it unless a roll
+/// against `messiness`, in percent, moves it to another column.
+pub struct GarbageGenerator {
+    rng: ChaCha8Rng,
+    messiness: u32,
+    hole: Option<usize>,
+}
+
+impl GarbageGenerator {
+    pub fn new(seed: u64, messiness: u32) -> GarbageGenerator {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        // the piece randomizer uses the first stream of the same seed
+        rng.set_stream(1);
+        GarbageGenerator {
+            rng,
+            messiness,
+            hole: None,
+        }
+    }
+
+    pub fn next_hole(&mut self) -> usize {
+        let hole = match self.hole {
+            Some(hole) if self.rng.gen_range(0..100) >= self.messiness => hole,
+            Some(hole) => (hole + self.rng.gen_range(1..BOARD_WIDTH)) % BOARD_WIDTH,
+            None => self.rng.gen_range(0..BOARD_WIDTH),
+        };
+        self.hole = Some(hole);
+        hole
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messiness_decides_how_often_the_hole_moves() {
+        let mut clean = GarbageGenerator::new(3, 0);
+        let first = clean.next_hole();
+        assert!((0..50).all(|_| clean.next_hole() == first));
+
+        let mut messy = GarbageGenerator::new(3, 100);
+        let mut last = messy.next_hole();
+        for _ in 0..50 {
+            let hole = messy.next_hole();
+            assert_ne!(hole, last);
+            assert!(hole < BOARD_WIDTH);
+            last = hole;
+        }
+    }
+}
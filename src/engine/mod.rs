@@ -0,0 +1,19 @@
+//! Rules of the game in plain grid coordinates, with no dependency on Bevy.
+//!
+//! The Bevy systems feed player input into a [`Game`] and mirror its state into sprites.
+
+mod board;
+mod game;
+mod garbage;
+mod piece;
+mod randomizer;
+mod rotation;
+mod rules;
+
+pub use board::*;
+pub use game::*;
+pub use garbage::*;
+pub use piece::*;
+pub use randomizer::*;
+pub use rotation::*;
+pub use rules::*;
@@ -0,0 +1,188 @@
+use crate::engine::RotationSystem;
+
+/// Column the piece origin spawns in. Pieces spawn one column right of the guideline position.
+pub const SPAWN_X: i32 = 5;
+/// Row the piece origin spawns in, counted from the bottom of the board.
+pub const SPAWN_Y: i32 = 18;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PieceKind {
+    Straight,
+    L,
+    ReverseL,
+    T,
+    Z,
+    ReverseZ,
+    Square,
+}
+
+impl PieceKind {
+    pub const ALL: [PieceKind; 7] = [
+        PieceKind::Straight,
+        PieceKind::L,
+        PieceKind::ReverseL,
+        PieceKind::T,
+        PieceKind::Z,
+        PieceKind::ReverseZ,
+        PieceKind::Square,
+    ];
+
+    /// The guideline letter of the piece: `I`, `O`, `T`, `S`, `Z`, `J` or `L`.
+    pub fn letter(&self) -> char {
+        match self {
+            PieceKind::Straight => 'I',
+            PieceKind::L => 'L',
+            PieceKind::ReverseL => 'J',
+            PieceKind::T => 'T',
+            PieceKind::Z => 'S',
+            PieceKind::ReverseZ => 'Z',
+            PieceKind::Square => 'O',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<PieceKind> {
+        PieceKind::ALL
+            .into_iter()
+            .find(|kind| kind.letter() == letter.to_ascii_uppercase())
+    }
+
+    /// Cell offsets relative to the piece origin for the given rotation state.
+    pub fn cells(&self, rotation: RotationState) -> [(i32, i32); 4] {
+        match self {
+            // https://tetris.fandom.com/wiki/SRS?file=SRS-pieces.png
+            // the straight piece rotates around the center of its 4x4 bounding box
+            PieceKind::Straight => match rotation {
+                RotationState::Zero => [(-1, 1), (0, 1), (1, 1), (2, 1)],
+                RotationState::R => [(1, 2), (1, 1), (1, 0), (1, -1)],
+                RotationState::Two => [(2, 0), (1, 0), (0, 0), (-1, 0)],
+                RotationState::L => [(0, -1), (0, 0), (0, 1), (0, 2)],
+            },
+            // the square piece rotates around the center of its 2x2 bounding box, so it never moves
+            PieceKind::Square => [(-1, 0), (0, 0), (-1, 1), (0, 1)],
+            _ => {
+                let mut cells = self.spawn_cells();
+                for _ in 0..rotation.clockwise_turns() {
+                    for cell in cells.iter_mut() {
+                        *cell = (cell.1, -cell.0);
+                    }
+                }
+                cells
+            }
+        }
+    }
+
+    fn spawn_cells(&self) -> [(i32, i32); 4] {
+        match self {
+            PieceKind::L => [(0, 0), (1, 0), (-1, 0), (1, 1)],
+            PieceKind::ReverseL => [(0, 0), (1, 0), (-1, 0), (-1, 1)],
+            PieceKind::T => [(0, 0), (0, 1), (1, 0), (-1, 0)],
+            PieceKind::Z => [(0, 0), (0, 1), (1, 1), (-1, 0)],
+            PieceKind::ReverseZ => [(0, 0), (0, 1), (-1, 1), (1, 0)],
+            PieceKind::Straight | PieceKind::Square => self.cells(RotationState::Zero),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RotationState {
+    #[default]
+    Zero, // zero rotates
+    R,   // clockwise or "right" rotation
+    L,   // counter-clockwise or "left" rotation
+    Two, // two rotations starting from zero
+}
+
+impl RotationState {
+    pub fn rotate(&self, is_clockwise: bool) -> RotationState {
+        if is_clockwise {
+            match self {
+                RotationState::Zero => RotationState::R,
+                RotationState::R => RotationState::Two,
+                RotationState::Two => RotationState::L,
+                RotationState::L => RotationState::Zero,
+            }
+        } else {
+            match self {
+                RotationState::Zero => RotationState::L,
+                RotationState::L => RotationState::Two,
+                RotationState::Two => RotationState::R,
+                RotationState::R => RotationState::Zero,
+            }
+        }
+    }
+
+    /// The state half a turn away.
+    pub fn flipped(&self) -> RotationState {
+        self.rotate(true).rotate(true)
+    }
+
+    pub fn clockwise_turns(&self) -> usize {
+        match self {
+            RotationState::Zero => 0,
+            RotationState::R => 1,
+            RotationState::Two => 2,
+            RotationState::L => 3,
+        }
+    }
+}
+
+/// A piece on the board. `x` and `y` are the grid position of the piece origin, with `y`
+/// counting up from the bottom row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Piece {
+    pub kind: PieceKind,
+    pub rotation: RotationState,
+    pub x: i32,
+    pub y: i32,
+    /// Shapes the piece takes as it turns.
+    pub system: RotationSystem,
+}
+
+impl Piece {
+    pub fn new(kind: PieceKind) -> Piece {
+        Piece {
+            kind,
+            rotation: RotationState::Zero,
+            x: SPAWN_X,
+            y: SPAWN_Y,
+            system: RotationSystem::default(),
+        }
+    }
+
+    /// A piece spawning in the spawn state of `system`.
+    pub fn with_system(kind: PieceKind, system: RotationSystem) -> Piece {
+        Piece {
+            system,
+            ..Piece::new(kind)
+        }
+    }
+
+    /// Absolute grid cells covered by the piece.
+    pub fn cells(&self) -> [(i32, i32); 4] {
+        self.system
+            .cells(self.kind, self.rotation)
+            .map(|(x, y)| (self.x + x, self.y + y))
+    }
+
+    pub fn shifted(&self, dx: i32, dy: i32) -> Piece {
+        Piece {
+            x: self.x + dx,
+            y: self.y + dy,
+            ..*self
+        }
+    }
+
+    pub fn rotated(&self, is_clockwise: bool) -> Piece {
+        Piece {
+            rotation: self.rotation.rotate(is_clockwise),
+            ..*self
+        }
+    }
+
+    pub fn flipped(&self) -> Piece {
+        Piece {
+            rotation: self.rotation.flipped(),
+            ..*self
+        }
+    }
+}
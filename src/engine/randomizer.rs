@@ -0,0 +1,276 @@
+use std::collections::VecDeque;
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::PieceKind;
+
+/// Source of new pieces. Implementations are seeded so the same seed always produces the same
+/// sequence.
+pub trait Randomizer: Send + Sync {
+    fn next(&mut self) -> PieceKind;
+}
+
+/// The available piece generators.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RandomizerKind {
+    /// Every piece is an independent 1 in 7 roll.
+    PureRandom,
+    /// NES style: roll once more if the piece repeats the previous one.
+    Nes,
+    /// TGM style: up to 6 rolls to find a piece that is not among the last 4.
+    Tgm,
+    /// Guideline: all seven pieces in a shuffled order before reshuffling.
+    #[default]
+    SevenBag,
+    /// Two copies of every piece in a shuffled bag of 14.
+    FourteenBag,
+}
+
+impl RandomizerKind {
+    pub fn build(&self, seed: u64) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::PureRandom => Box::new(PureRandom::new(seed)),
+            RandomizerKind::Nes => Box::new(NesRandomizer::new(seed)),
+            RandomizerKind::Tgm => Box::new(HistoryRandomizer::new(seed, 4, 6)),
+            RandomizerKind::SevenBag => Box::new(BagRandomizer::new(seed, 1)),
+            RandomizerKind::FourteenBag => Box::new(BagRandomizer::new(seed, 2)),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RandomizerKind> {
+        match name {
+            "random" => Some(RandomizerKind::PureRandom),
+            "nes" => Some(RandomizerKind::Nes),
+            "tgm" => Some(RandomizerKind::Tgm),
+            "bag7" => Some(RandomizerKind::SevenBag),
+            "bag14" => Some(RandomizerKind::FourteenBag),
+            _ => None,
+        }
+    }
+}
+
+fn roll(rng: &mut ChaCha8Rng) -> PieceKind {
+    PieceKind::ALL[rng.gen_range(0..PieceKind::ALL.len())]
+}
+
+pub struct PureRandom {
+    rng: ChaCha8Rng,
+}
+
+impl PureRandom {
+    pub fn new(seed: u64) -> PureRandom {
+        PureRandom {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Randomizer for PureRandom {
+    fn next(&mut self) -> PieceKind {
+        roll(&mut self.rng)
+    }
+}
+
+pub struct NesRandomizer {
+    rng: ChaCha8Rng,
+    last: Option<PieceKind>,
+}
+
+impl NesRandomizer {
+    pub fn new(seed: u64) -> NesRandomizer {
+        NesRandomizer {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            last: None,
+        }
+    }
+}
+
+impl Randomizer for NesRandomizer {
+    fn next(&mut self) -> PieceKind {
+        // the first roll has an eighth "reroll" outcome, just like a repeat of the last piece
+        let first_roll = self.rng.gen_range(0..=PieceKind::ALL.len());
+        let piece_kind = match PieceKind::ALL.get(first_roll) {
+            Some(&piece_kind) if Some(piece_kind) != self.last => piece_kind,
+            _ => roll(&mut self.rng),
+        };
+
+        self.last = Some(piece_kind);
+        piece_kind
+    }
+}
+
+pub struct HistoryRandomizer {
+    rng: ChaCha8Rng,
+    history: VecDeque<PieceKind>,
+    rolls: usize,
+    is_first: bool,
+}
+
+impl HistoryRandomizer {
+    pub fn new(seed: u64, history_size: usize, rolls: usize) -> HistoryRandomizer {
+        // the history starts filled with snakes so those are unlikely early on
+        let history = [PieceKind::ReverseZ, PieceKind::Z]
+            .into_iter()
+            .cycle()
+            .take(history_size)
+            .collect();
+
+        HistoryRandomizer {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            history,
+            rolls,
+            is_first: true,
+        }
+    }
+}
+
+impl Randomizer for HistoryRandomizer {
+    fn next(&mut self) -> PieceKind {
+        let piece_kind = if self.is_first {
+            // never start with a piece that forces an overhang
+            self.is_first = false;
+            *[
+                PieceKind::Straight,
+                PieceKind::L,
+                PieceKind::ReverseL,
+                PieceKind::T,
+            ]
+            .choose(&mut self.rng)
+            .unwrap()
+        } else {
+            let mut piece_kind = roll(&mut self.rng);
+            for _ in 1..self.rolls {
+                if !self.history.contains(&piece_kind) {
+                    break;
+                }
+                piece_kind = roll(&mut self.rng);
+            }
+            piece_kind
+        };
+
+        self.history.pop_front();
+        self.history.push_back(piece_kind);
+        piece_kind
+    }
+}
+
+/// Deals `copies` of every piece in a shuffled order before reshuffling.
+pub struct BagRandomizer {
+    rng: ChaCha8Rng,
+    bag: Vec<PieceKind>,
+    copies: usize,
+}
+
+impl BagRandomizer {
+    pub fn new(seed: u64, copies: usize) -> BagRandomizer {
+        BagRandomizer {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            bag: Vec::with_capacity(PieceKind::ALL.len() * copies),
+            copies,
+        }
+    }
+}
+
+impl Randomizer for BagRandomizer {
+    fn next(&mut self) -> PieceKind {
+        if self.bag.is_empty() {
+            for _ in 0..self.copies {
+                self.bag.extend(PieceKind::ALL);
+            }
+            self.bag.shuffle(&mut self.rng);
+        }
+
+        self.bag.pop().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: usize = 70_000;
+
+    fn deal(kind: RandomizerKind, count: usize) -> Vec<PieceKind> {
+        let mut randomizer = kind.build(42);
+        (0..count).map(|_| randomizer.next()).collect()
+    }
+
+    fn assert_uniform(pieces: &[PieceKind]) {
+        let expected = pieces.len() as f64 / PieceKind::ALL.len() as f64;
+        for piece_kind in PieceKind::ALL {
+            let count = pieces.iter().filter(|&&p| p == piece_kind).count() as f64;
+            assert!(
+                (count - expected).abs() < expected * 0.05,
+                "{:?} dealt {} times, expected about {}",
+                piece_kind,
+                count,
+                expected
+            );
+        }
+    }
+
+    fn repeat_rate(pieces: &[PieceKind]) -> f64 {
+        let repeats = pieces.windows(2).filter(|pair| pair[0] == pair[1]).count();
+        repeats as f64 / (pieces.len() - 1) as f64
+    }
+
+    #[test]
+    fn same_seed_deals_same_sequence() {
+        for kind in [
+            RandomizerKind::PureRandom,
+            RandomizerKind::Nes,
+            RandomizerKind::Tgm,
+            RandomizerKind::SevenBag,
+            RandomizerKind::FourteenBag,
+        ] {
+            assert_eq!(deal(kind, 100), deal(kind, 100), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn pure_random_distribution() {
+        let pieces = deal(RandomizerKind::PureRandom, SAMPLES);
+        assert_uniform(&pieces);
+        assert!((repeat_rate(&pieces) - 1.0 / 7.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn nes_distribution() {
+        let pieces = deal(RandomizerKind::Nes, SAMPLES);
+        assert_uniform(&pieces);
+        // a repeat needs the reroll to land on the same piece: 2/8 * 1/7
+        assert!((repeat_rate(&pieces) - 1.0 / 28.0).abs() < 0.005);
+    }
+
+    #[test]
+    fn tgm_distribution() {
+        let pieces = deal(RandomizerKind::Tgm, SAMPLES);
+        assert_uniform(&pieces);
+        assert!(repeat_rate(&pieces) < 0.02);
+        assert!(![PieceKind::Z, PieceKind::ReverseZ, PieceKind::Square].contains(&pieces[0]));
+    }
+
+    #[test]
+    fn seven_bag_distribution() {
+        let pieces = deal(RandomizerKind::SevenBag, SAMPLES);
+        assert_uniform(&pieces);
+        for bag in pieces.chunks(7) {
+            for piece_kind in PieceKind::ALL {
+                assert_eq!(bag.iter().filter(|&&p| p == piece_kind).count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn fourteen_bag_distribution() {
+        let pieces = deal(RandomizerKind::FourteenBag, SAMPLES);
+        assert_uniform(&pieces);
+        for bag in pieces.chunks(14) {
+            for piece_kind in PieceKind::ALL {
+                assert_eq!(bag.iter().filter(|&&p| p == piece_kind).count(), 2);
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{PieceKind, RotationState};
+
+/// How pieces are shaped in each rotation state and how they kick off walls and the stack.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RotationSystem {
+    /// The guideline Super Rotation System, with five kick tests per rotation.
+    #[default]
+    Srs,
+    /// SRS with I kicks that mirror each other, so the I behaves the same turning either way.
+    SrsPlus,
+    /// Arika's TGM rotation: pieces sit flat side up at the bottom of their box and only kick one
+    /// column right or left, unless the centre column is in the way.
+    Ars,
+    /// The NES rotation: no kicks, and I, S and Z keep to the right of centre when upright.
+    Nrs,
+}
+
+impl RotationSystem {
+    pub fn from_name(name: &str) -> Option<RotationSystem> {
+        match name {
+            "srs" => Some(RotationSystem::Srs),
+            "srs+" => Some(RotationSystem::SrsPlus),
+            "ars" => Some(RotationSystem::Ars),
+            "nrs" => Some(RotationSystem::Nrs),
+            _ => None,
+        }
+    }
+
+    /// Cell offsets relative to the piece origin for the given rotation state.
+    pub fn cells(&self, kind: PieceKind, rotation: RotationState) -> [(i32, i32); 4] {
+        match self {
+            RotationSystem::Srs | RotationSystem::SrsPlus => kind.cells(rotation),
+            RotationSystem::Ars => ars_cells(kind, rotation),
+            RotationSystem::Nrs => nrs_cells(kind, rotation),
+        }
+    }
+}
+
+/// The kicks tried when turning a piece half around. The [`RotationSystem`] only covers quarter
+/// turns.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Kicks180 {
+    /// The 180 kicks of SRS+, nudging the piece up and then to the sides.
+    #[default]
+    SrsPlus,
+    /// Only turn in place.
+    None,
+}
+
+impl Kicks180 {
+    pub fn from_name(name: &str) -> Option<Kicks180> {
+        match name {
+            "srs+" => Some(Kicks180::SrsPlus),
+            "none" => Some(Kicks180::None),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a piece with only two shapes, lying and upright, is upright in `rotation`.
+fn is_upright(rotation: RotationState) -> bool {
+    matches!(rotation, RotationState::R | RotationState::L)
+}
+
+/// Turns `cells` clockwise around the origin as often as `rotation` is from `Zero`.
+fn turned(mut cells: [(i32, i32); 4], rotation: RotationState) -> [(i32, i32); 4] {
+    for _ in 0..rotation.clockwise_turns() {
+        for cell in cells.iter_mut() {
+            *cell = (cell.1, -cell.0);
+        }
+    }
+    cells
+}
+
+// https://tetris.wiki/ARS
+// pieces lie flat side up as low in the 3x3 box around the origin as they go, the straight piece
+// across the second row of a 4x4 box
+fn ars_cells(kind: PieceKind, rotation: RotationState) -> [(i32, i32); 4] {
+    match (kind, rotation) {
+        (PieceKind::Straight, r) if is_upright(r) => [(1, 1), (1, 0), (1, -1), (1, -2)],
+        (PieceKind::Straight, _) => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+        (PieceKind::Square, _) => [(-1, 0), (0, 0), (-1, -1), (0, -1)],
+        (PieceKind::Z, r) if is_upright(r) => [(-1, 1), (-1, 0), (0, 0), (0, -1)],
+        (PieceKind::Z, _) => [(0, 0), (1, 0), (-1, -1), (0, -1)],
+        (PieceKind::ReverseZ, r) if is_upright(r) => [(1, 1), (0, 0), (1, 0), (0, -1)],
+        (PieceKind::ReverseZ, _) => [(-1, 0), (0, 0), (0, -1), (1, -1)],
+        (PieceKind::T, RotationState::Zero) => [(-1, 0), (0, 0), (1, 0), (0, -1)],
+        (PieceKind::T, RotationState::R) => [(0, 1), (-1, 0), (0, 0), (0, -1)],
+        (PieceKind::T, RotationState::Two) => [(0, 0), (-1, -1), (0, -1), (1, -1)],
+        (PieceKind::T, RotationState::L) => [(0, 1), (0, 0), (1, 0), (0, -1)],
+        (PieceKind::L, RotationState::Zero) => [(-1, 0), (0, 0), (1, 0), (-1, -1)],
+        (PieceKind::L, RotationState::R) => [(-1, 1), (0, 1), (0, 0), (0, -1)],
+        (PieceKind::L, RotationState::Two) => [(1, 0), (-1, -1), (0, -1), (1, -1)],
+        (PieceKind::L, RotationState::L) => [(0, 1), (0, 0), (0, -1), (1, -1)],
+        (PieceKind::ReverseL, RotationState::Zero) => [(-1, 0), (0, 0), (1, 0), (1, -1)],
+        (PieceKind::ReverseL, RotationState::R) => [(0, 1), (0, 0), (-1, -1), (0, -1)],
+        (PieceKind::ReverseL, RotationState::Two) => [(-1, 0), (-1, -1), (0, -1), (1, -1)],
+        (PieceKind::ReverseL, RotationState::L) => [(0, 1), (1, 1), (0, 0), (0, -1)],
+    }
+}
+
+// https://tetris.wiki/Nintendo_Rotation_System
+// pieces turn around their centre cell, pointing down as they spawn
+fn nrs_cells(kind: PieceKind, rotation: RotationState) -> [(i32, i32); 4] {
+    match kind {
+        PieceKind::Straight if is_upright(rotation) => [(0, 2), (0, 1), (0, 0), (0, -1)],
+        PieceKind::Straight => [(-2, 0), (-1, 0), (0, 0), (1, 0)],
+        PieceKind::Square => [(-1, 0), (0, 0), (-1, -1), (0, -1)],
+        PieceKind::Z if is_upright(rotation) => [(0, 1), (0, 0), (1, 0), (1, -1)],
+        PieceKind::Z => [(0, 0), (1, 0), (-1, -1), (0, -1)],
+        PieceKind::ReverseZ if is_upright(rotation) => [(1, 1), (0, 0), (1, 0), (0, -1)],
+        PieceKind::ReverseZ => [(-1, 0), (0, 0), (0, -1), (1, -1)],
+        PieceKind::T => turned([(-1, 0), (0, 0), (1, 0), (0, -1)], rotation),
+        PieceKind::L => turned([(-1, 0), (0, 0), (1, 0), (-1, -1)], rotation),
+        PieceKind::ReverseL => turned([(-1, 0), (0, 0), (1, 0), (1, -1)], rotation),
+    }
+}
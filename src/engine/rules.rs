@@ -0,0 +1,688 @@
+use crate::engine::{Board, Kicks180, Piece, PieceKind, RotationState, RotationSystem};
+
+static KICK_VALUES: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 0->R
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R-> 0
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R-> 2
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 2->R
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 2-> L
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L->2
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L-> 0
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 0->L
+];
+
+static I_KICK_VALUES: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // 0->R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // R-> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // R->2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // 2->R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // 2-> L
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // L->2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // L-> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // 0->L
+];
+
+/// SRS+ I kicks: the ones turning left mirror the ones turning right.
+static SRS_PLUS_I_KICK_VALUES: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (1, 0), (-2, 0), (-2, -1), (1, 2)], // 0->R
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)], // R-> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // R->2
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)], // 2->R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // 2-> L
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)], // L->2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // L-> 0
+    [(0, 0), (-1, 0), (2, 0), (2, -1), (-1, 2)], // 0->L
+];
+
+static SRS_PLUS_180_KICK_VALUES: [[(i32, i32); 6]; 4] = [
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)], // 0->2
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],   // R->L
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)], // 2->0
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)], // L->R
+];
+
+static ARS_KICK_VALUES: [(i32, i32); 3] = [(0, 0), (1, 0), (-1, 0)];
+
+static NO_KICKS: [(i32, i32); 1] = [(0, 0)];
+
+/// Wall kick offsets to try, in order, when turning a quarter from `current_state` to
+/// `next_state`.
+pub fn get_kick_values(
+    system: RotationSystem,
+    piece_kind: PieceKind,
+    current_state: RotationState,
+    next_state: RotationState,
+) -> &'static [(i32, i32)] {
+    let index = match (current_state, next_state) {
+        (RotationState::Zero, RotationState::R) => 0,
+        (RotationState::R, RotationState::Zero) => 1,
+        (RotationState::R, RotationState::Two) => 2,
+        (RotationState::Two, RotationState::R) => 3,
+        (RotationState::Two, RotationState::L) => 4,
+        (RotationState::L, RotationState::Two) => 5,
+        (RotationState::L, RotationState::Zero) => 6,
+        (RotationState::Zero, RotationState::L) => 7,
+        _ => panic!("not a quarter turn, see get_180_kick_values"),
+    };
+
+    match (system, piece_kind) {
+        // the square piece turns in place, so it never needs a kick
+        (_, PieceKind::Square) | (RotationSystem::Nrs, _) => &NO_KICKS,
+        (RotationSystem::Srs, PieceKind::Straight) => &I_KICK_VALUES[index],
+        (RotationSystem::SrsPlus, PieceKind::Straight) => &SRS_PLUS_I_KICK_VALUES[index],
+        (RotationSystem::Srs | RotationSystem::SrsPlus, _) => &KICK_VALUES[index],
+        (RotationSystem::Ars, PieceKind::Straight) => &NO_KICKS,
+        (RotationSystem::Ars, _) => &ARS_KICK_VALUES,
+    }
+}
+
+/// Wall kick offsets to try, in order, when turning half around from `current_state`.
+pub fn get_180_kick_values(
+    kicks: Kicks180,
+    system: RotationSystem,
+    piece_kind: PieceKind,
+    current_state: RotationState,
+) -> &'static [(i32, i32)] {
+    let index = current_state.clockwise_turns();
+    match (kicks, system, piece_kind) {
+        (Kicks180::None, _, _) | (_, RotationSystem::Nrs, _) | (_, _, PieceKind::Square) => {
+            &NO_KICKS
+        }
+        (Kicks180::SrsPlus, _, _) => &SRS_PLUS_180_KICK_VALUES[index],
+    }
+}
+
+/// The ARS centre column rule: an L, J or T turned into the stack may not kick when the first of
+/// its cells in the way, reading its box row by row from the top left, is in the middle column.
+pub fn centre_column_blocked(board: &Board, rotated: &Piece) -> bool {
+    if !matches!(
+        rotated.kind,
+        PieceKind::L | PieceKind::ReverseL | PieceKind::T
+    ) {
+        return false;
+    }
+
+    let mut blocked: Vec<_> = rotated
+        .cells()
+        .into_iter()
+        .filter(|&(x, y)| board.is_occupied(x, y))
+        .collect();
+    blocked.sort_by_key(|&(x, y)| (-y, x));
+    blocked.first().is_some_and(|&(x, _)| x == rotated.x)
+}
+
+/// How a T piece was spun into place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Spin {
+    Mini,
+    Full,
+}
+
+/// How a rotation put the piece in place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Kick {
+    /// Index of the kick in the table the rotation used.
+    pub index: usize,
+    /// The rotation was a half turn, kicked with the 180 table.
+    pub half_turn: bool,
+}
+
+/// The cell of a T the other three touch, and which way the nub sticks out of it. Worked out from
+/// the cells rather than the rotation, as each rotation system has the T face its own way.
+fn t_center_and_nub(piece: &Piece) -> ((i32, i32), (i32, i32)) {
+    let cells = piece.cells();
+    let touching =
+        |(ax, ay): (i32, i32), (bx, by): (i32, i32)| (ax - bx).abs() + (ay - by).abs() == 1;
+    let center = *cells
+        .iter()
+        .find(|&&cell| cells.iter().filter(|&&other| touching(cell, other)).count() == 3)
+        .expect("a T has a cell the other three touch");
+    // the one neighbour with nothing opposite it
+    let nub = cells
+        .iter()
+        .map(|&(x, y)| (x - center.0, y - center.1))
+        .find(|&(dx, dy)| (dx, dy) != (0, 0) && !cells.contains(&(center.0 - dx, center.1 - dy)))
+        .expect("a T has a nub");
+    (center, nub)
+}
+
+/// Checks a T piece for a T-spin with the 3-corner rule: it got into place by rotating, `kick` being
+/// the kick that rotation used, and at least three of the four cells diagonal to its center are
+/// blocked. It is a full T-spin when both corners it points at are blocked, or when a quarter turn
+/// needed the last SRS kick, and a mini otherwise.
+pub fn t_spin(board: &Board, piece: &Piece, kick: Option<Kick>) -> Option<Spin> {
+    let kick = kick?;
+    if piece.kind != PieceKind::T {
+        return None;
+    }
+
+    let ((x, y), (nx, ny)) = t_center_and_nub(piece);
+    let blocked = |dx: i32, dy: i32| board.is_occupied(x + dx, y + dy);
+    let corners = [(-1, 1), (1, 1), (1, -1), (-1, -1)];
+    if corners.iter().filter(|&&(dx, dy)| blocked(dx, dy)).count() < 3 {
+        return None;
+    }
+
+    // the two corners on the side of the T's nub
+    let front = [(nx + ny, ny + nx), (nx - ny, ny - nx)];
+    let srs = matches!(piece.system, RotationSystem::Srs | RotationSystem::SrsPlus);
+    let last_srs_kick = srs && !kick.half_turn && kick.index == 4;
+    if front.iter().all(|&(dx, dy)| blocked(dx, dy)) || last_srs_kick {
+        Some(Spin::Full)
+    } else {
+        Some(Spin::Mini)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Cell, Game, GameEvent, Input, BOARD_WIDTH, VISIBLE_HEIGHT};
+
+    const ROTATIONS: [RotationState; 4] = [
+        RotationState::Zero,
+        RotationState::R,
+        RotationState::Two,
+        RotationState::L,
+    ];
+
+    const SYSTEMS: [RotationSystem; 4] = [
+        RotationSystem::Srs,
+        RotationSystem::SrsPlus,
+        RotationSystem::Ars,
+        RotationSystem::Nrs,
+    ];
+
+    /// Every rotation with whether it turns clockwise.
+    const TRANSITIONS: [(RotationState, bool); 8] = [
+        (RotationState::Zero, true),
+        (RotationState::R, false),
+        (RotationState::R, true),
+        (RotationState::Two, false),
+        (RotationState::Two, true),
+        (RotationState::L, false),
+        (RotationState::L, true),
+        (RotationState::Zero, false),
+    ];
+
+    /// The SRS offsets of the guideline, by rotation in the order of [`ROTATIONS`]. The kicks of a
+    /// rotation are the offsets of the state it leaves minus those of the state it reaches.
+    fn srs_offsets(kind: PieceKind) -> [[(i32, i32); 5]; 4] {
+        match kind {
+            PieceKind::Straight => [
+                [(0, 0), (-1, 0), (2, 0), (-1, 0), (2, 0)],
+                [(-1, 0), (0, 0), (0, 0), (0, 1), (0, -2)],
+                [(-1, 1), (1, 1), (-2, 1), (1, 0), (-2, 0)],
+                [(0, 1), (0, 1), (0, 1), (0, -1), (0, 2)],
+            ],
+            PieceKind::Square => [[(0, 0); 5], [(0, -1); 5], [(-1, -1); 5], [(-1, 0); 5]],
+            _ => [
+                [(0, 0); 5],
+                [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+                [(0, 0); 5],
+                [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+            ],
+        }
+    }
+
+    /// Twice the point a piece turns around, relative to its origin.
+    fn doubled_center(kind: PieceKind) -> (i32, i32) {
+        match kind {
+            PieceKind::Straight => (1, 1),
+            PieceKind::Square => (-1, 1),
+            _ => (0, 0),
+        }
+    }
+
+    fn index(rotation: RotationState) -> usize {
+        ROTATIONS.iter().position(|&r| r == rotation).unwrap()
+    }
+
+    fn sorted(mut cells: [(i32, i32); 4]) -> [(i32, i32); 4] {
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn pieces_turn_around_their_true_center() {
+        for kind in PieceKind::ALL {
+            let (cx, cy) = doubled_center(kind);
+            for rotation in ROTATIONS {
+                let turned = kind.cells(rotation).map(|(x, y)| {
+                    let (x, y) = (2 * x - cx, 2 * y - cy);
+                    ((y + cx) / 2, (-x + cy) / 2)
+                });
+                assert_eq!(
+                    sorted(turned),
+                    sorted(kind.cells(rotation.rotate(true))),
+                    "{:?} turning clockwise from {:?}",
+                    kind,
+                    rotation
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn kicks_match_the_srs_offsets() {
+        for kind in PieceKind::ALL {
+            let offsets = srs_offsets(kind);
+            for (from, clockwise) in TRANSITIONS {
+                let to = from.rotate(clockwise);
+                let (from_offsets, to_offsets) = (offsets[index(from)], offsets[index(to)]);
+                // the offsets move the piece around the pivot SRS turns it about, the kicks around
+                // its true center, so only the difference to the first test carries over
+                let (bx, by) = (
+                    from_offsets[0].0 - to_offsets[0].0,
+                    from_offsets[0].1 - to_offsets[0].1,
+                );
+                let mut expected: Vec<_> = from_offsets
+                    .iter()
+                    .zip(&to_offsets)
+                    .map(|(f, t)| (f.0 - t.0 - bx, f.1 - t.1 - by))
+                    .collect();
+                // the square piece only has the one test it repeats
+                expected.dedup();
+                assert_eq!(
+                    get_kick_values(RotationSystem::Srs, kind, from, to).to_vec(),
+                    expected,
+                    "{:?} {:?} -> {:?}",
+                    kind,
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    /// A board full but for the cells of `piece` rotated and moved by `kick`, so every other test
+    /// is blocked and the rotation has to take that kick. Nothing checks the cells the piece turns
+    /// from, so they can stay filled.
+    fn kick_board(piece: &Piece, clockwise: bool, kick: (i32, i32)) -> Board {
+        let kicked = piece.rotated(clockwise).shifted(kick.0, kick.1);
+        let mut board = Board::default();
+        for y in 0..VISIBLE_HEIGHT as i32 {
+            for x in 0..BOARD_WIDTH as i32 {
+                if !kicked.cells().contains(&(x, y)) {
+                    board.set(x, y, Some(Cell::Garbage));
+                }
+            }
+        }
+        board
+    }
+
+    #[test]
+    fn rotations_take_the_first_kick_that_fits() {
+        for system in SYSTEMS {
+            for kind in PieceKind::ALL {
+                for (from, clockwise) in TRANSITIONS {
+                    let piece = Piece {
+                        rotation: from,
+                        x: 4,
+                        y: 8,
+                        ..Piece::with_system(kind, system)
+                    };
+                    let kicks = get_kick_values(system, kind, from, from.rotate(clockwise));
+                    for (kick, &(dx, dy)) in kicks.iter().enumerate() {
+                        let mut game = Game::default();
+                        game.board = kick_board(&piece, clockwise, (dx, dy));
+                        game.active = Some(piece);
+                        if kick > 0 && centre_column_blocked(&game.board, &piece.rotated(clockwise))
+                        {
+                            // covered by arika_pieces_do_not_kick_off_the_centre_column
+                            continue;
+                        }
+
+                        let input = if clockwise {
+                            Input::RotateClockwise
+                        } else {
+                            Input::RotateCounterClockwise
+                        };
+                        assert_eq!(
+                            game.step(input),
+                            vec![GameEvent::Rotated { kick }],
+                            "{:?} {:?} {:?} clockwise: {}, test {}",
+                            system,
+                            kind,
+                            from,
+                            clockwise,
+                            kick + 1
+                        );
+                        assert_eq!(game.active, Some(piece.rotated(clockwise).shifted(dx, dy)));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn srs_plus_i_kicks_mirror_each_other() {
+        let mirrored = |rotation: RotationState| match rotation {
+            RotationState::R => RotationState::L,
+            RotationState::L => RotationState::R,
+            rotation => rotation,
+        };
+        for (from, clockwise) in TRANSITIONS {
+            let to = from.rotate(clockwise);
+            let kicks = get_kick_values(RotationSystem::SrsPlus, PieceKind::Straight, from, to);
+            let mirror: Vec<_> = get_kick_values(
+                RotationSystem::SrsPlus,
+                PieceKind::Straight,
+                mirrored(from),
+                mirrored(to),
+            )
+            .iter()
+            .map(|&(x, y)| (-x, y))
+            .collect();
+            assert_eq!(kicks.to_vec(), mirror, "{:?} -> {:?}", from, to);
+        }
+    }
+
+    #[test]
+    fn arika_pieces_do_not_kick_off_the_centre_column() {
+        // an L lying flat under a cell, which it runs into turning upright
+        let piece = Piece {
+            x: 1,
+            y: 1,
+            ..Piece::with_system(PieceKind::L, RotationSystem::Ars)
+        };
+        let mut game = Game::default();
+        game.board.set(1, 2, Some(Cell::Garbage));
+        game.active = Some(piece);
+
+        assert!(game.step(Input::RotateClockwise).is_empty());
+        assert_eq!(game.active, Some(piece));
+
+        // a column over the cell is not in the middle of the L, so it kicks away from it
+        let mut game = Game::default();
+        game.board.set(0, 2, Some(Cell::Garbage));
+        game.active = Some(piece);
+
+        assert_eq!(
+            game.step(Input::RotateClockwise),
+            vec![GameEvent::Rotated { kick: 1 }]
+        );
+        assert_eq!(game.active, Some(piece.rotated(true).shifted(1, 0)));
+    }
+
+    #[test]
+    fn nintendo_pieces_never_kick() {
+        let piece = Piece {
+            rotation: RotationState::R,
+            x: 0,
+            ..Piece::with_system(PieceKind::T, RotationSystem::Nrs)
+        };
+        let mut game = Game::default();
+        game.active = Some(piece);
+
+        assert!(game.step(Input::RotateClockwise).is_empty());
+        assert_eq!(game.active, Some(piece));
+    }
+
+    /// `cells` moved so the lowest of the leftmost is at the origin.
+    fn normalized(cells: [(i32, i32); 4]) -> [(i32, i32); 4] {
+        let cells = sorted(cells);
+        cells.map(|(x, y)| (x - cells[0].0, y - cells[0].1))
+    }
+
+    #[test]
+    fn every_system_shapes_the_same_pieces() {
+        for system in SYSTEMS {
+            for kind in PieceKind::ALL {
+                for rotation in ROTATIONS {
+                    let shape = normalized(system.cells(kind, rotation));
+                    assert!(
+                        ROTATIONS
+                            .iter()
+                            .any(|&srs| normalized(kind.cells(srs)) == shape),
+                        "{:?} {:?} {:?}",
+                        system,
+                        kind,
+                        rotation
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn half_turns_flip_pieces_in_place() {
+        for kind in PieceKind::ALL {
+            for rotation in ROTATIONS {
+                let piece = Piece {
+                    rotation,
+                    x: 4,
+                    y: 8,
+                    ..Piece::new(kind)
+                };
+                let mut game = Game::default();
+                game.active = Some(piece);
+
+                assert_eq!(
+                    game.step(Input::Rotate180),
+                    vec![GameEvent::Rotated { kick: 0 }]
+                );
+                assert_eq!(game.active.unwrap().rotation, rotation.flipped());
+                assert_eq!((game.active.unwrap().x, game.active.unwrap().y), (4, 8));
+            }
+        }
+    }
+
+    #[test]
+    fn half_turns_kick_off_the_floor_with_their_own_table() {
+        // a T lying on the floor has to move up a row to point down
+        let piece = Piece {
+            x: 4,
+            y: 0,
+            ..Piece::new(PieceKind::T)
+        };
+        let mut game = Game::default();
+        game.active = Some(piece);
+
+        assert_eq!(
+            game.step(Input::Rotate180),
+            vec![GameEvent::Rotated { kick: 1 }]
+        );
+        assert_eq!(game.active, Some(piece.flipped().shifted(0, 1)));
+
+        game.kicks_180 = Kicks180::None;
+        game.active = Some(piece);
+        assert!(game.step(Input::Rotate180).is_empty());
+        assert_eq!(game.active, Some(piece));
+    }
+
+    #[test]
+    fn rotation_fails_when_no_kick_fits() {
+        let piece = Piece {
+            x: 4,
+            y: 8,
+            ..Piece::new(PieceKind::Straight)
+        };
+        let mut game = Game::default();
+        for x in 0..BOARD_WIDTH as i32 {
+            for y in 0..VISIBLE_HEIGHT as i32 {
+                if y != 9 {
+                    game.board.set(x, y, Some(Cell::Garbage));
+                }
+            }
+        }
+        game.active = Some(piece);
+
+        assert!(game.step(Input::RotateClockwise).is_empty());
+        assert_eq!(game.active, Some(piece));
+    }
+
+    /// Turns a T from `rotation` at `(x, y)` on `rows` and locks it where the turn put it.
+    /// Returns the kick the turn took, the spin and the rows cleared.
+    fn spin_t(
+        rows: &[&str],
+        (rotation, x, y): (RotationState, i32, i32),
+        input: Input,
+    ) -> (usize, Option<Spin>, usize) {
+        let mut game = Game::default();
+        game.board = Board::drawn(rows);
+        game.active = Some(Piece {
+            rotation,
+            x,
+            y,
+            ..Piece::new(PieceKind::T)
+        });
+        let kick = match game.step(input)[..] {
+            [GameEvent::Rotated { kick }] => kick,
+            ref events => panic!("the T didn't turn: {:?}", events),
+        };
+        game.step(Input::HardDrop);
+        let lines = game.last_clear.map_or(0, |clear| clear.rows.len());
+        (kick, game.last_spin, lines)
+    }
+
+    #[test]
+    fn t_spin_double_points_into_a_covered_slot() {
+        let rows = ["...#......", "###...####", "####.#####"];
+
+        assert_eq!(
+            spin_t(&rows, (RotationState::R, 4, 1), Input::RotateClockwise),
+            (0, Some(Spin::Full), 2)
+        );
+
+        // dropped in without turning, with nothing over the slot, is no spin
+        let mut game = Game::default();
+        game.board = Board::drawn(&rows[1..]);
+        game.active = Some(Piece {
+            rotation: RotationState::Two,
+            x: 4,
+            y: 8,
+            ..Piece::new(PieceKind::T)
+        });
+        game.step(Input::HardDrop);
+        assert_eq!(game.last_spin, None);
+        assert_eq!(game.last_clear.unwrap().rows, vec![0, 1]);
+    }
+
+    #[test]
+    fn t_spin_triple_kicks_down_under_the_roof() {
+        let rows = [
+            "....#.....",
+            "..........",
+            "####.#####",
+            "###..#####",
+            "####.#####",
+        ];
+
+        assert_eq!(
+            spin_t(
+                &rows,
+                (RotationState::Zero, 3, 3),
+                Input::RotateCounterClockwise
+            ),
+            (4, Some(Spin::Full), 3)
+        );
+    }
+
+    #[test]
+    fn t_spin_mini_has_one_corner_in_front() {
+        let rows = ["#..#......", "...#######"];
+
+        assert_eq!(
+            spin_t(&rows, (RotationState::L, 2, 1), Input::RotateClockwise),
+            (2, Some(Spin::Mini), 1)
+        );
+    }
+
+    #[test]
+    fn the_last_kick_makes_a_full_t_spin_of_a_mini() {
+        // only the corner behind the T's nub and one beside it are blocked, as in a mini
+        let rows = [
+            ".#........",
+            "..........",
+            "#.#.......",
+            "#..#######",
+            "#..#######",
+        ];
+
+        assert_eq!(
+            spin_t(&rows, (RotationState::Zero, 2, 3), Input::RotateClockwise),
+            (4, Some(Spin::Full), 1)
+        );
+    }
+
+    #[test]
+    fn only_the_last_quarter_turn_srs_kick_makes_a_full_t_spin() {
+        let board = Board::drawn(&[
+            ".#........",
+            "..........",
+            "#.#.......",
+            "#..#######",
+            "#..#######",
+        ]);
+        let spin = |system, index, half_turn| {
+            let piece = Piece {
+                rotation: RotationState::R,
+                x: 1,
+                y: 1,
+                ..Piece::with_system(PieceKind::T, system)
+            };
+            t_spin(&board, &piece, Some(Kick { index, half_turn }))
+        };
+
+        assert_eq!(spin(RotationSystem::Srs, 4, false), Some(Spin::Full));
+        assert_eq!(spin(RotationSystem::SrsPlus, 4, false), Some(Spin::Full));
+        assert_eq!(spin(RotationSystem::Srs, 3, false), Some(Spin::Mini));
+        // the 180 table has more kicks, and its fifth is nothing special
+        assert_eq!(spin(RotationSystem::SrsPlus, 4, true), Some(Spin::Mini));
+    }
+
+    #[test]
+    fn t_spins_face_the_way_each_system_points_the_t() {
+        // the direction of the nub in each rotation, from Zero to L
+        let up_first = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+        let down_first = [(0, -1), (-1, 0), (0, 1), (1, 0)];
+        let systems = [
+            (RotationSystem::Srs, up_first),
+            (RotationSystem::SrsPlus, up_first),
+            (RotationSystem::Ars, down_first),
+            (RotationSystem::Nrs, down_first),
+        ];
+        for (system, nubs) in systems {
+            for (rotation, (nx, ny)) in ROTATIONS.into_iter().zip(nubs) {
+                let piece = Piece {
+                    rotation,
+                    x: 4,
+                    y: 5,
+                    ..Piece::with_system(PieceKind::T, system)
+                };
+                // the arms cancel out, leaving four times the center plus the nub
+                let (sx, sy) = piece
+                    .cells()
+                    .iter()
+                    .fold((0, 0), |(sx, sy), &(x, y)| (sx + x, sy + y));
+                let (x, y) = ((sx - nx) / 4, (sy - ny) / 4);
+                let board_with = |corners: [(i32, i32); 3]| {
+                    let mut board = Board::default();
+                    for (dx, dy) in corners {
+                        board.set(x + dx, y + dy, Some(Cell::Garbage));
+                    }
+                    board
+                };
+                let front = [(nx + ny, ny + nx), (nx - ny, ny - nx)];
+                let back = [(-nx - ny, -ny - nx), (ny - nx, nx - ny)];
+
+                let kick = Some(Kick {
+                    index: 0,
+                    half_turn: false,
+                });
+                let spins = [
+                    t_spin(&board_with([front[0], front[1], back[0]]), &piece, kick),
+                    t_spin(&board_with([back[0], back[1], front[0]]), &piece, kick),
+                ];
+                assert_eq!(
+                    spins,
+                    [Some(Spin::Full), Some(Spin::Mini)],
+                    "{:?} {:?}",
+                    system,
+                    rotation
+                );
+            }
+        }
+    }
+}
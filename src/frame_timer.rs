@@ -0,0 +1,48 @@
+use bevy::time::TimerMode;
+
+/// Counts simulation ticks. The fixed-step counterpart of [`bevy::time::Timer`], so timing does not
+/// depend on the frame rate and plays back the same in replays.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameTimer {
+    duration: u32,
+    elapsed: u32,
+    mode: TimerMode,
+    just_finished: bool,
+}
+
+impl FrameTimer {
+    pub fn new(duration: u32, mode: TimerMode) -> FrameTimer {
+        FrameTimer {
+            duration,
+            elapsed: 0,
+            mode,
+            just_finished: false,
+        }
+    }
+
+    /// Advances the timer by one tick.
+    pub fn tick(&mut self) -> &Self {
+        self.just_finished = false;
+        if self.finished() && self.mode == TimerMode::Once {
+            return self;
+        }
+
+        self.elapsed += 1;
+        if self.elapsed >= self.duration {
+            self.just_finished = true;
+            if self.mode == TimerMode::Repeating {
+                self.elapsed = 0;
+            }
+        }
+        self
+    }
+
+    pub fn finished(&self) -> bool {
+        self.just_finished || self.elapsed >= self.duration
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = 0;
+        self.just_finished = false;
+    }
+}
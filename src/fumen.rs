@@ -0,0 +1,594 @@
+use bevy::prelude::*;
+
+use crate::{
+    engine::{Board, Cell, Game, Piece, PieceKind, RotationState, BOARD_WIDTH, VISIBLE_HEIGHT},
+    Action, Editor, Engine, GameMode, GameState, KeyBindings, RestartGameEvent, Settings,
+};
+
+/// Characters fumen writes its numbers in, 6 bits to a character with the lowest first.
+const ENCODE_TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Marks the data of a version 1.15 diagram, after `v`, `m` or `d` depending on the viewer.
+const VERSION: &str = "115@";
+
+/// Rows of a fumen field above the garbage row under it.
+const FIELD_HEIGHT: i32 = 23;
+
+/// Cells of a fumen field, garbage row included.
+const FIELD_BLOCKS: u32 = (FIELD_HEIGHT as u32 + 1) * BOARD_WIDTH as u32;
+
+/// Guideline letters of the fumen piece numbers 1 to 7. 0 is an empty cell and 8 garbage.
+const PIECE_LETTERS: &str = "ILOZTJS";
+const GARBAGE: u32 = 8;
+
+/// Rotations by their fumen number.
+const ROTATIONS: [RotationState; 4] = [
+    RotationState::Two,
+    RotationState::R,
+    RotationState::Zero,
+    RotationState::L,
+];
+
+/// Comments are written in printable ASCII, from a space up.
+const COMMENT_BASE: u32 = 96;
+
+const QUIZ_PREFIX: &str = "#Q=";
+
+/// The first page of a fumen diagram: a board, the piece it drops, and the hold and queue of its
+/// `#Q=[H](C)NEXT` quiz comment.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fumen {
+    pub board: Board,
+    /// The piece the page drops, where it comes to rest.
+    pub active: Option<Piece>,
+    pub hold: Option<PieceKind>,
+    /// Pieces dealt after the active one, or from the start without one.
+    pub queue: Vec<PieceKind>,
+}
+
+impl Fumen {
+    pub fn from_game(game: &Game) -> Fumen {
+        Fumen {
+            board: game.board.clone(),
+            active: game.active,
+            hold: game.hold,
+            queue: game.next.iter().copied().collect(),
+        }
+    }
+
+    /// A game on the fumen board with its hold piece. The active piece is dealt like the queue,
+    /// from the spawn.
+    pub fn game(&self) -> Game {
+        let mut game = Game::default();
+        game.board = self.board.clone();
+        game.hold = self.hold;
+        game
+    }
+
+    /// Pieces in the order they are dealt, the active one first.
+    pub fn pieces(&self) -> Vec<PieceKind> {
+        self.active
+            .map(|piece| piece.kind)
+            .into_iter()
+            .chain(self.queue.iter().copied())
+            .collect()
+    }
+
+    pub fn encode(&self) -> String {
+        let mut data = String::new();
+        encode_field(&self.board, &mut data);
+
+        let comment = (self.active.is_some() || self.hold.is_some() || !self.queue.is_empty())
+            .then(|| self.quiz());
+        let (kind, rotation, position) = self
+            .active
+            .and_then(|piece| {
+                let (rotation, position) = fumen_position(piece)?;
+                Some((piece_number(piece.kind), rotation, position))
+            })
+            .unwrap_or((0, 0, 0));
+        // every flag but the comment is left as a new diagram sets it: locking the piece and
+        // colouring the pieces on the board
+        let flags = ((comment.is_some() as u32) << 3) | (1 << 2);
+        let action = ((flags * FIELD_BLOCKS + position) * 4 + rotation) * 8 + kind;
+        push_value(&mut data, action, 3);
+
+        if let Some(comment) = comment {
+            let escaped = escape(&comment);
+            push_value(&mut data, escaped.len() as u32, 2);
+            for chunk in escaped.as_bytes().chunks(4) {
+                let value = chunk
+                    .iter()
+                    .rev()
+                    .fold(0, |value, &c| value * COMMENT_BASE + (c as u32 - 32));
+                push_value(&mut data, value, 5);
+            }
+        }
+
+        format!("v{}{}", VERSION, split_lines(&data))
+    }
+
+    /// Reads the first page of a diagram, also out of a whole fumen URL. Blocks of the garbage row
+    /// under the field are dropped, and blocks above the visible board refused.
+    pub fn decode(fumen: &str) -> Result<Fumen, String> {
+        let data = fumen
+            .split_once(VERSION)
+            .map(|(_, data)| data)
+            .ok_or("only version 1.15 diagrams can be read")?;
+        let mut reader = Reader {
+            data: data.trim().chars().filter(|&c| c != '?').collect(),
+            read: 0,
+        };
+
+        let mut board = Board::default();
+        let mut block = 0;
+        while block < FIELD_BLOCKS {
+            let value = reader.read(2)?;
+            // the first page is written against an empty field, so a block can only be added
+            let number = (value / FIELD_BLOCKS)
+                .checked_sub(8)
+                .filter(|&number| number <= GARBAGE)
+                .ok_or("the field does not start a diagram")?;
+            let count = value % FIELD_BLOCKS + 1;
+            if count == FIELD_BLOCKS && number == 0 {
+                // how many more pages repeat the empty field
+                reader.read(1)?;
+            }
+            for _ in 0..count {
+                if block == FIELD_BLOCKS {
+                    return Err("the field has too many blocks".to_string());
+                }
+                let (x, y) = block_position(block);
+                if number != 0 && y >= VISIBLE_HEIGHT as i32 {
+                    return Err(format!("the field is more than {} rows", VISIBLE_HEIGHT));
+                }
+                board.set(x, y, block_cell(number)?);
+                block += 1;
+            }
+        }
+
+        let action = reader.read(3)?;
+        let kind = action % 8;
+        let rotation = ROTATIONS[(action / 8 % 4) as usize];
+        let position = action / 32 % FIELD_BLOCKS;
+        let has_comment = (action / 32 / FIELD_BLOCKS) & (1 << 3) != 0;
+        let active = match block_cell(kind)? {
+            Some(Cell::Piece(kind)) => Some(decode_piece(kind, rotation, position)),
+            Some(Cell::Garbage) => return Err("garbage is not a piece".to_string()),
+            None => None,
+        };
+
+        let mut fumen = Fumen {
+            board,
+            active,
+            ..Fumen::default()
+        };
+        if has_comment {
+            let len = reader.read(2)? as usize;
+            let mut comment = String::with_capacity(len);
+            while comment.len() < len {
+                let mut value = reader.read(5)?;
+                for _ in 0..4.min(len - comment.len()) {
+                    comment.push(char::from_u32(value % COMMENT_BASE + 32).unwrap());
+                    value /= COMMENT_BASE;
+                }
+            }
+            if let Some(quiz) = Quiz::parse(&unescape(&comment))? {
+                fumen.hold = quiz.hold;
+                fumen.queue = match (active, quiz.current) {
+                    (None, Some(current)) => std::iter::once(current).chain(quiz.next).collect(),
+                    _ => quiz.next,
+                };
+            }
+        }
+        Ok(fumen)
+    }
+
+    fn quiz(&self) -> String {
+        let mut queue = self.queue.iter().copied();
+        let current = match self.active {
+            Some(piece) => Some(piece.kind),
+            None => queue.next(),
+        };
+        Quiz {
+            hold: self.hold,
+            current,
+            next: queue.collect(),
+        }
+        .to_string()
+    }
+}
+
+/// Hold, current piece and queue of a `#Q=[H](C)NEXT` quiz comment.
+struct Quiz {
+    hold: Option<PieceKind>,
+    current: Option<PieceKind>,
+    next: Vec<PieceKind>,
+}
+
+impl Quiz {
+    fn parse(comment: &str) -> Result<Option<Quiz>, String> {
+        let quiz = match comment.strip_prefix(QUIZ_PREFIX) {
+            Some(quiz) => quiz,
+            None => return Ok(None),
+        };
+        let invalid = || format!("{:?} is not a quiz", comment);
+        let (hold, quiz) = quiz
+            .strip_prefix('[')
+            .and_then(|quiz| quiz.split_once(']'))
+            .ok_or_else(invalid)?;
+        let (current, next) = quiz
+            .strip_prefix('(')
+            .and_then(|quiz| quiz.split_once(')'))
+            .ok_or_else(invalid)?;
+
+        let piece = |letter: char| {
+            PieceKind::from_letter(letter).ok_or_else(|| format!("{:?} is not a piece", letter))
+        };
+        Ok(Some(Quiz {
+            hold: hold.chars().next().map(piece).transpose()?,
+            current: current.chars().next().map(piece).transpose()?,
+            next: next.chars().map(piece).collect::<Result<_, _>>()?,
+        }))
+    }
+}
+
+impl std::fmt::Display for Quiz {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let letter =
+            |kind: Option<PieceKind>| kind.map_or(String::new(), |kind| kind.letter().into());
+        let next: String = self.next.iter().map(PieceKind::letter).collect();
+        write!(
+            f,
+            "{}[{}]({}){}",
+            QUIZ_PREFIX,
+            letter(self.hold),
+            letter(self.current),
+            next
+        )
+    }
+}
+
+/// Reads fumen numbers off the data of a diagram.
+struct Reader {
+    data: Vec<char>,
+    read: usize,
+}
+
+impl Reader {
+    fn read(&mut self, chars: usize) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..chars {
+            let c = *self
+                .data
+                .get(self.read)
+                .ok_or("the diagram ends too early")?;
+            let digit = ENCODE_TABLE
+                .iter()
+                .position(|&d| d as char == c)
+                .ok_or_else(|| format!("{:?} is not diagram data", c))?;
+            value += (digit as u32) << (6 * i);
+            self.read += 1;
+        }
+        Ok(value)
+    }
+}
+
+fn push_value(data: &mut String, mut value: u32, chars: usize) {
+    for _ in 0..chars {
+        data.push(ENCODE_TABLE[(value % 64) as usize] as char);
+        value /= 64;
+    }
+}
+
+/// Breaks the data up with `?` the way fumen does, so long diagrams wrap on old forums.
+fn split_lines(data: &str) -> String {
+    if data.len() < 41 {
+        return data.to_string();
+    }
+    let (head, tail) = data.split_at(42.min(data.len()));
+    std::iter::once(head)
+        .chain(
+            tail.as_bytes()
+                .chunks(47)
+                .map(|chunk| std::str::from_utf8(chunk).unwrap()),
+        )
+        .collect::<Vec<_>>()
+        .join("?")
+}
+
+/// Blocks run from the top left of the field, row by row down to the garbage row at `y` -1.
+fn block_position(block: u32) -> (i32, i32) {
+    let width = BOARD_WIDTH as u32;
+    (
+        (block % width) as i32,
+        FIELD_HEIGHT - 1 - (block / width) as i32,
+    )
+}
+
+fn encode_field(board: &Board, data: &mut String) {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for block in 0..FIELD_BLOCKS {
+        let (x, y) = block_position(block);
+        let number = match board.get(x, y) {
+            Some(Cell::Piece(kind)) => piece_number(kind),
+            Some(Cell::Garbage) => GARBAGE,
+            None => 0,
+        };
+        match runs.last_mut() {
+            Some((last, count)) if *last == number => *count += 1,
+            _ => runs.push((number, 1)),
+        }
+    }
+
+    for &(number, count) in &runs {
+        push_value(data, (number + 8) * FIELD_BLOCKS + count - 1, 2);
+    }
+    if runs == [(0, FIELD_BLOCKS)] {
+        // no more pages repeat the empty field
+        push_value(data, 0, 1);
+    }
+}
+
+fn piece_number(kind: PieceKind) -> u32 {
+    PIECE_LETTERS.find(kind.letter()).unwrap() as u32 + 1
+}
+
+fn block_cell(number: u32) -> Result<Option<Cell>, String> {
+    match number {
+        0 => Ok(None),
+        GARBAGE => Ok(Some(Cell::Garbage)),
+        number => PIECE_LETTERS
+            .chars()
+            .nth(number as usize - 1)
+            .and_then(PieceKind::from_letter)
+            .map(|kind| Some(kind.into()))
+            .ok_or_else(|| format!("{} is not a block", number)),
+    }
+}
+
+/// Cells of a piece around the block fumen places it by.
+fn fumen_cells(kind: PieceKind, rotation: RotationState) -> [(i32, i32); 4] {
+    let spawn = match kind {
+        PieceKind::Straight => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+        PieceKind::T => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+        PieceKind::Square => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        PieceKind::L => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+        PieceKind::ReverseL => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+        PieceKind::Z => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+        PieceKind::ReverseZ => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+    };
+    // fumen keeps these where they were before turning halfway round, the O everywhere
+    let (dx, dy) = match (kind, rotation) {
+        (PieceKind::Square, RotationState::Zero) => (0, -1),
+        (PieceKind::Square, RotationState::Two) => (1, 0),
+        (PieceKind::Square, RotationState::L) => (1, -1),
+        (PieceKind::Straight, RotationState::Two) => (1, 0),
+        (PieceKind::Straight, RotationState::L) => (0, -1),
+        (PieceKind::Z, RotationState::Zero) | (PieceKind::ReverseZ, RotationState::Zero) => (0, -1),
+        (PieceKind::Z, RotationState::R) => (-1, 0),
+        (PieceKind::ReverseZ, RotationState::L) => (1, 0),
+        _ => (0, 0),
+    };
+    spawn.map(|(x, y)| {
+        let (x, y) = match rotation {
+            RotationState::Zero => (x, y),
+            RotationState::R => (y, -x),
+            RotationState::Two => (-x, -y),
+            RotationState::L => (-y, x),
+        };
+        (x + dx, y + dy)
+    })
+}
+
+/// How far `cells` are from `shape`, the same cells moved.
+fn offset(mut shape: [(i32, i32); 4], mut cells: [(i32, i32); 4]) -> (i32, i32) {
+    shape.sort();
+    cells.sort();
+    (cells[0].0 - shape[0].0, cells[0].1 - shape[0].1)
+}
+
+/// The fumen rotation and position of a piece, if it lies inside the field.
+fn fumen_position(piece: Piece) -> Option<(u32, u32)> {
+    let inside = |&(x, y): &(i32, i32)| {
+        (0..BOARD_WIDTH as i32).contains(&x) && (0..FIELD_HEIGHT).contains(&y)
+    };
+    if !piece.cells().iter().all(inside) {
+        return None;
+    }
+    // pieces of other rotation systems are written with the fumen rotation of the same shape,
+    // trying their own first
+    let cells = piece.cells();
+    let same_shape = |&&rotation: &&RotationState| {
+        let shape = fumen_cells(piece.kind, rotation);
+        let (dx, dy) = offset(shape, cells);
+        let mut shifted = shape.map(|(x, y)| (x + dx, y + dy));
+        let mut cells = cells;
+        shifted.sort();
+        cells.sort();
+        shifted == cells
+    };
+    let rotation = std::iter::once(&piece.rotation)
+        .chain(ROTATIONS.iter())
+        .find(same_shape)
+        .copied()
+        .unwrap_or(piece.rotation);
+    let (x, y) = offset(fumen_cells(piece.kind, rotation), cells);
+    let rotation = ROTATIONS.iter().position(|&r| r == rotation).unwrap() as u32;
+    Some((
+        rotation,
+        (FIELD_HEIGHT - 1 - y) as u32 * BOARD_WIDTH as u32 + x as u32,
+    ))
+}
+
+fn decode_piece(kind: PieceKind, rotation: RotationState, position: u32) -> Piece {
+    let (x, y) = block_position(position);
+    let cells = fumen_cells(kind, rotation).map(|(dx, dy)| (x + dx, y + dy));
+    let piece = Piece {
+        rotation,
+        x: 0,
+        y: 0,
+        ..Piece::new(kind)
+    };
+    let (dx, dy) = offset(piece.cells(), cells);
+    piece.shifted(dx, dy)
+}
+
+/// Escapes `text` like JavaScript's `escape`, which fumen comments are written with.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for unit in text.encode_utf16() {
+        match char::from_u32(unit as u32) {
+            Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => escaped.push(c),
+            _ if unit < 256 => escaped += &format!("%{:02X}", unit),
+            _ => escaped += &format!("%u{:04X}", unit),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut units = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let escaped = match rest.strip_prefix("%u") {
+            Some(unicode) => unicode.get(..4).map(|digits| (digits, 6)),
+            None => rest
+                .strip_prefix('%')
+                .and_then(|latin| latin.get(..2))
+                .map(|digits| (digits, 3)),
+        };
+        match escaped.and_then(|(digits, len)| Some((u16::from_str_radix(digits, 16).ok()?, len))) {
+            Some((unit, len)) => {
+                units.push(unit);
+                rest = &rest[len..];
+            }
+            None => {
+                units.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// Prints the current board, or the one being set up in the editor, as a fumen diagram and copies
+/// it to the clipboard.
+pub fn export_fumen(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    game_state: Res<State<GameState>>,
+    engine: Res<Engine>,
+    editor: Res<Editor>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::ExportFumen) {
+        return;
+    }
+
+    let fumen = match game_state.get() {
+        GameState::Editor => editor.setup.encode(),
+        _ => Fumen::from_game(&engine).encode(),
+    };
+    println!("fumen: {}", fumen);
+    if let Err(err) = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(fumen))
+    {
+        println!("could not copy the fumen: {}", err);
+    }
+}
+
+/// Starts practising on the fumen diagram in the clipboard.
+pub fn import_fumen(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut settings: ResMut<Settings>,
+    mut ev_restart: EventWriter<RestartGameEvent>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::ImportFumen) {
+        return;
+    }
+
+    let fumen = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|err| err.to_string())
+        .and_then(|text| Fumen::decode(&text));
+    match fumen {
+        Ok(fumen) => {
+            settings.ruleset.mode = GameMode::Endless;
+            settings.fumen = Some(fumen);
+            settings.fumen_imported = true;
+            ev_restart.send(RestartGameEvent);
+        }
+        Err(err) => println!("could not import a fumen: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_field_encodes_like_fumen() {
+        assert_eq!(Fumen::default().encode(), "v115@vhAAgH");
+        assert_eq!(Fumen::decode("v115@vhAAgH"), Ok(Fumen::default()));
+    }
+
+    #[test]
+    fn reads_blocks_from_the_top_down() {
+        let fumen = Fumen::decode("https://fumen.zui.jp/?v115@9gF8DeF8DeF8DeF8NeAgH").unwrap();
+
+        for y in 0..VISIBLE_HEIGHT as i32 {
+            for x in 0..BOARD_WIDTH as i32 {
+                let garbage = x < 6 && y < 4;
+                assert_eq!(fumen.board.get(x, y), garbage.then_some(Cell::Garbage));
+            }
+        }
+        assert_eq!(fumen.encode(), "v115@9gF8DeF8DeF8DeF8NeAgH");
+    }
+
+    #[test]
+    fn pieces_and_quiz_survive_a_round_trip() {
+        let mut board = Board::default();
+        for x in 0..9 {
+            board.set(x, 0, Some(Cell::Garbage));
+        }
+        board.set(4, 1, Some(PieceKind::T.into()));
+        board.set(0, 1, Some(PieceKind::Straight.into()));
+
+        for kind in PieceKind::ALL {
+            for rotation in ROTATIONS {
+                let active = Piece {
+                    rotation,
+                    x: 4,
+                    y: 6,
+                    ..Piece::new(kind)
+                };
+                let fumen = Fumen {
+                    board: board.clone(),
+                    active: Some(active),
+                    hold: Some(PieceKind::Square),
+                    queue: vec![PieceKind::Z, PieceKind::ReverseL],
+                };
+                assert_eq!(Fumen::decode(&fumen.encode()), Ok(fumen));
+            }
+        }
+    }
+
+    #[test]
+    fn quiz_without_a_piece_deals_its_current_piece_first() {
+        let fumen = Fumen {
+            queue: vec![PieceKind::T, PieceKind::Straight],
+            ..Fumen::default()
+        };
+        let decoded = Fumen::decode(&fumen.encode()).unwrap();
+
+        assert_eq!(decoded.active, None);
+        assert_eq!(decoded.pieces(), vec![PieceKind::T, PieceKind::Straight]);
+    }
+
+    #[test]
+    fn comments_escape_like_javascript() {
+        assert_eq!(escape("#Q=[](T)IO"), "%23Q%3D%5B%5D%28T%29IO");
+        assert_eq!(unescape("%23Q%3D%5B%5D%28T%29IO"), "#Q=[](T)IO");
+        assert_eq!(unescape(&escape("é ✓")), "é ✓");
+    }
+}
@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+
+use crate::{
+    cell_translation,
+    engine::{GameEvent, GarbageGenerator, BOARD_WIDTH},
+    DigProgress, Engine, GameClock, GameMode, GameOverEvent, PiecePartBundle, PieceRandomizer,
+    PieceType, Placed, PlacedPieces, Settings, SurvivalProgress, SQUARE_SIZE,
+};
+
+/// Rows of garbage a dig keeps on the board while it has garbage left to give.
+pub const GARBAGE_HEIGHT: u32 = 10;
+
+/// Ticks between rising rows in survival, for each minute of the game. Later minutes keep the
+/// last.
+const SURVIVAL_RISE_TICKS: [u32; 7] = [480, 360, 300, 240, 180, 120, 90];
+
+/// Chance in percent that a rising row moves its hole away from the one below.
+const SURVIVAL_MESSINESS: u32 = 30;
+
+/// Holes of the garbage rows in the current game.
+#[derive(Resource)]
+pub struct Garbage(pub GarbageGenerator);
+
+impl Default for Garbage {
+    fn default() -> Self {
+        Garbage(GarbageGenerator::new(0, 0))
+    }
+}
+
+/// Pushes a row of garbage in at the bottom for each of `holes`, on the engine board and in the
+/// sprites mirroring it.
+pub fn push_garbage(
+    commands: &mut Commands,
+    engine: &mut Engine,
+    placed_pieces: &mut PlacedPieces,
+    placed_query: &mut Query<&mut Transform, With<Placed>>,
+    holes: &[usize],
+) -> Vec<GameEvent> {
+    let events = engine.push_garbage(holes);
+
+    for entity in placed_pieces.0.iter().flatten().flatten() {
+        if let Ok(mut transform) = placed_query.get_mut(*entity) {
+            transform.translation.y += holes.len() as f32 * SQUARE_SIZE;
+        }
+    }
+    for (i, &hole) in holes.iter().enumerate() {
+        // whatever is pushed out of the top has ended the game in the engine already
+        for entity in placed_pieces.0.remove(0).into_iter().flatten() {
+            commands.entity(entity).despawn();
+        }
+
+        let y = (holes.len() - 1 - i) as i32;
+        let row = (0..BOARD_WIDTH)
+            .map(|x| {
+                (x != hole).then(|| {
+                    let translation = cell_translation(x as i32, y);
+                    commands
+                        .spawn((
+                            PiecePartBundle::new(&PieceType::Garbage, &translation),
+                            Placed,
+                        ))
+                        .id()
+                })
+            })
+            .collect();
+        placed_pieces.0.push(row);
+    }
+
+    events
+}
+
+/// Tops the garbage of a dig back up to [`GARBAGE_HEIGHT`] rows between pieces, until all its
+/// lines have been pushed in. The first rows go in as the game starts.
+#[allow(clippy::too_many_arguments)]
+pub fn keep_garbage_height(
+    settings: Res<Settings>,
+    randomizer: Res<PieceRandomizer>,
+    mut commands: Commands,
+    mut engine: ResMut<Engine>,
+    mut garbage: ResMut<Garbage>,
+    mut progress: ResMut<DigProgress>,
+    mut placed_pieces: ResMut<PlacedPieces>,
+    mut placed_query: Query<&mut Transform, With<Placed>>,
+    mut ev_game_over: EventWriter<GameOverEvent>,
+) {
+    let (lines, messiness) = match settings.ruleset.mode {
+        GameMode::Dig { lines, messiness } => (lines, messiness),
+        _ => return,
+    };
+    if engine.is_game_over() {
+        return;
+    }
+    if progress.given == 0 {
+        garbage.0 = GarbageGenerator::new(randomizer.seed, messiness);
+    } else if engine.active.is_some() || !engine.clearing_rows().is_empty() {
+        return;
+    }
+
+    let on_board = engine.board.garbage_rows() as u32;
+    let rows = GARBAGE_HEIGHT
+        .saturating_sub(on_board)
+        .min(lines - progress.given);
+    if rows == 0 {
+        return;
+    }
+
+    let holes: Vec<usize> = (0..rows).map(|_| garbage.0.next_hole()).collect();
+    progress.given += rows;
+    let events = push_garbage(
+        &mut commands,
+        &mut engine,
+        &mut placed_pieces,
+        &mut placed_query,
+        &holes,
+    );
+    if events.contains(&GameEvent::GameOver) {
+        ev_game_over.send(GameOverEvent);
+    }
+}
+
+/// Pushes a row of garbage in under the stack and the active piece on a timer that speeds up every
+/// minute. Rows waiting to rise hold off while cleared rows are still disappearing.
+#[allow(clippy::too_many_arguments)]
+pub fn rise_garbage(
+    settings: Res<Settings>,
+    randomizer: Res<PieceRandomizer>,
+    clock: Res<GameClock>,
+    mut commands: Commands,
+    mut engine: ResMut<Engine>,
+    mut garbage: ResMut<Garbage>,
+    mut progress: ResMut<SurvivalProgress>,
+    mut placed_pieces: ResMut<PlacedPieces>,
+    mut placed_query: Query<&mut Transform, With<Placed>>,
+    mut ev_game_over: EventWriter<GameOverEvent>,
+) {
+    if settings.ruleset.mode != GameMode::Survival || engine.is_game_over() {
+        return;
+    }
+    if progress.rows == 0 && progress.waited == 0 {
+        garbage.0 = GarbageGenerator::new(randomizer.seed, SURVIVAL_MESSINESS);
+    }
+
+    let minute = (clock.0 / 3600) as usize;
+    let interval = SURVIVAL_RISE_TICKS[minute.min(SURVIVAL_RISE_TICKS.len() - 1)];
+    progress.waited += 1;
+    if progress.waited < interval || !engine.clearing_rows().is_empty() {
+        return;
+    }
+
+    progress.waited = 0;
+    progress.rows += 1;
+    let hole = garbage.0.next_hole();
+    let events = push_garbage(
+        &mut commands,
+        &mut engine,
+        &mut placed_pieces,
+        &mut placed_query,
+        &[hole],
+    );
+    if events.contains(&GameEvent::GameOver) {
+        ev_game_over.send(GameOverEvent);
+    }
+}
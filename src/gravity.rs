@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+
+/// Gravity is counted in 1/65536ths of a row, so slow levels fall less than a row per tick.
+pub const SUBROWS: u32 = 65536;
+
+/// Drops a piece to the floor within the tick it spawns.
+pub const TWENTY_G: u32 = 20 * SUBROWS;
+
+/// Ticks per row of the endless curve, from level 1. Later levels keep the last speed.
+const ENDLESS_FRAMES_PER_ROW: [u32; 19] = [
+    36, 33, 30, 27, 24, 21, 18, 15, 12, 9, 9, 9, 6, 6, 6, 3, 3, 3, 1,
+];
+
+/// Seconds per row of the guideline curve `(0.8 - (level - 1) * 0.007)^(level - 1)`, from level 1.
+/// Later levels are 20G.
+const GUIDELINE_SECONDS_PER_ROW: [f64; 18] = [
+    1.0, 0.793, 0.6178, 0.47273, 0.3552, 0.262, 0.18968, 0.13473, 0.09388, 0.06415, 0.04298,
+    0.02822, 0.01815, 0.01144, 0.00706, 0.00426, 0.00252, 0.00146,
+];
+
+/// Gravity of `level` on the endless curve.
+pub fn endless_gravity(level: u32) -> u32 {
+    let i = (level.max(1) as usize - 1).min(ENDLESS_FRAMES_PER_ROW.len() - 1);
+    SUBROWS.div_ceil(ENDLESS_FRAMES_PER_ROW[i])
+}
+
+/// Gravity of `level` on the guideline curve.
+pub fn guideline_gravity(level: u32) -> u32 {
+    match GUIDELINE_SECONDS_PER_ROW.get(level.max(1) as usize - 1) {
+        Some(seconds) => ((SUBROWS as f64 / (seconds * 60.0)).ceil() as u32).min(TWENTY_G),
+        None => TWENTY_G,
+    }
+}
+
+/// How fast the active piece falls on its own, and how far it has got towards the next row.
+#[derive(Resource)]
+pub struct Gravity {
+    per_tick: u32,
+    progress: u32,
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity::new(endless_gravity(1))
+    }
+}
+
+impl Gravity {
+    /// Falls `per_tick` / [`SUBROWS`] rows every tick.
+    pub fn new(per_tick: u32) -> Gravity {
+        Gravity {
+            per_tick,
+            progress: 0,
+        }
+    }
+
+    pub fn set(&mut self, per_tick: u32) {
+        self.per_tick = per_tick;
+    }
+
+    /// Advances one tick and returns how many rows to fall.
+    pub fn tick(&mut self) -> u32 {
+        self.progress += self.per_tick;
+        let rows = self.progress / SUBROWS;
+        self.progress %= SUBROWS;
+        rows
+    }
+
+    /// At 20G a piece lands the moment it spawns or moves over a gap.
+    pub fn is_instant(&self) -> bool {
+        self.per_tick >= TWENTY_G
+    }
+
+    pub fn reset(&mut self) {
+        self.progress = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endless_curve_keeps_the_old_speeds() {
+        // level 1 fell a row every 36 ticks and level 10 every 9
+        let mut gravity = Gravity::new(endless_gravity(1));
+        assert_eq!((0..36).map(|_| gravity.tick()).sum::<u32>(), 1);
+        assert_eq!(SUBROWS.div_ceil(endless_gravity(10)), 9);
+        assert_eq!(endless_gravity(30), SUBROWS);
+    }
+
+    #[test]
+    fn guideline_curve_speeds_up_to_twenty_g() {
+        assert!((1..19).all(|level| guideline_gravity(level) < guideline_gravity(level + 1)));
+        assert!(guideline_gravity(15) > 2 * SUBROWS);
+        assert_eq!(guideline_gravity(19), TWENTY_G);
+
+        let mut gravity = Gravity::new(guideline_gravity(20));
+        assert_eq!(gravity.tick(), 20);
+    }
+}
@@ -0,0 +1,306 @@
+use std::{collections::BTreeMap, error::Error, fs, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameOverEvent, ReplayPlayback, ReplayRecorder};
+
+const KEY_BINDINGS_PATH: &str = "controls.ron";
+
+/// Everything the player can do with the keyboard during a game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    Rotate180,
+    Hold,
+    Pause,
+    Restart,
+    MuteMusic,
+    ExportFumen,
+    ImportFumen,
+}
+
+impl Action {
+    /// Actions on the falling piece, sampled into [`PlayerInput`] every simulation tick.
+    pub const PIECE: [Action; 8] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateClockwise,
+        Action::RotateCounterClockwise,
+        Action::Rotate180,
+        Action::Hold,
+    ];
+
+    pub const ALL: [Action; 13] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateClockwise,
+        Action::RotateCounterClockwise,
+        Action::Rotate180,
+        Action::Hold,
+        Action::Pause,
+        Action::Restart,
+        Action::MuteMusic,
+        Action::ExportFumen,
+        Action::ImportFumen,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::SoftDrop => "Soft drop",
+            Action::HardDrop => "Hard drop",
+            Action::RotateClockwise => "Rotate right",
+            Action::RotateCounterClockwise => "Rotate left",
+            Action::Rotate180 => "Rotate 180",
+            Action::Hold => "Hold",
+            Action::Pause => "Pause",
+            Action::Restart => "Restart",
+            Action::MuteMusic => "Mute music",
+            Action::ExportFumen => "Export fumen",
+            Action::ImportFumen => "Import fumen",
+        }
+    }
+
+    fn default_keys(&self) -> Vec<KeyCode> {
+        match self {
+            Action::MoveLeft => vec![KeyCode::ArrowLeft],
+            Action::MoveRight => vec![KeyCode::ArrowRight],
+            Action::SoftDrop => vec![KeyCode::ArrowDown],
+            Action::HardDrop => vec![KeyCode::Space],
+            Action::RotateClockwise => vec![KeyCode::KeyZ],
+            Action::RotateCounterClockwise => vec![KeyCode::KeyX],
+            Action::Rotate180 => vec![KeyCode::KeyA],
+            Action::Hold => vec![KeyCode::KeyC],
+            Action::Pause => vec![KeyCode::Escape],
+            Action::Restart => vec![KeyCode::KeyR],
+            Action::MuteMusic => vec![KeyCode::KeyM],
+            Action::ExportFumen => vec![KeyCode::KeyE],
+            Action::ImportFumen => vec![KeyCode::KeyI],
+        }
+    }
+
+    /// Bit of the action in [`PlayerInput`]. Only the actions in [`Action::PIECE`] have one, the
+    /// others are read straight from the keyboard.
+    fn bit(&self) -> Option<u8> {
+        Action::PIECE
+            .iter()
+            .position(|action| action == self)
+            .map(|index| 1 << index)
+    }
+}
+
+/// The keys bound to each action, kept in `controls.ron` between sessions. An action can have any
+/// number of keys, and a key belongs to one action at most.
+#[derive(Resource, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyBindings(BTreeMap<Action, Vec<KeyCode>>);
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings(
+            Action::ALL
+                .iter()
+                .map(|action| (*action, action.default_keys()))
+                .collect(),
+        )
+    }
+}
+
+impl KeyBindings {
+    /// Loads the bindings, starting from the defaults if there is no file yet. Actions missing
+    /// from the file keep their default keys.
+    pub fn load() -> KeyBindings {
+        let path = Path::new(KEY_BINDINGS_PATH);
+        if !path.exists() {
+            return KeyBindings::default();
+        }
+        let loaded = fs::read_to_string(path)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|bindings| Ok(ron::from_str::<KeyBindings>(&bindings)?));
+        match loaded {
+            Ok(loaded) => {
+                let mut bindings = KeyBindings::default();
+                bindings.0.extend(loaded.0);
+                bindings
+            }
+            Err(err) => {
+                println!("could not load key bindings: {}", err);
+                KeyBindings::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let result = ron::to_string(self)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|bindings| Ok(fs::write(KEY_BINDINGS_PATH, bindings)?));
+        if let Err(err) = result {
+            println!("could not save key bindings: {}", err);
+        }
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn pressed(&self, keyboard_input: &ButtonInput<KeyCode>, action: Action) -> bool {
+        keyboard_input.any_pressed(self.keys(action).iter().copied())
+    }
+
+    pub fn just_pressed(&self, keyboard_input: &ButtonInput<KeyCode>, action: Action) -> bool {
+        keyboard_input.any_just_pressed(self.keys(action).iter().copied())
+    }
+
+    /// Unbinds `key` from `action` if it is bound to it, and otherwise binds it to `action`
+    /// instead of whatever it was bound to before.
+    pub fn toggle(&mut self, action: Action, key: KeyCode) {
+        if self.keys(action).contains(&key) {
+            self.0
+                .entry(action)
+                .or_default()
+                .retain(|&bound| bound != key);
+            return;
+        }
+        for keys in self.0.values_mut() {
+            keys.retain(|&bound| bound != key);
+        }
+        self.0.entry(action).or_default().push(key);
+    }
+}
+
+/// The actions held and newly pressed during one simulation tick. Gameplay systems read this
+/// instead of the keyboard so a tick can be driven by a replay just the same.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerInput {
+    pressed: u8,
+    just_pressed: u8,
+}
+
+impl PlayerInput {
+    pub fn pressed(&self, action: Action) -> bool {
+        action.bit().is_some_and(|bit| self.pressed & bit != 0)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        action.bit().is_some_and(|bit| self.just_pressed & bit != 0)
+    }
+
+    /// Marks `action` as pressed on this tick. Actions outside [`Action::PIECE`] are ignored.
+    pub fn press(&mut self, action: Action) {
+        if let Some(bit) = action.bit() {
+            self.pressed |= bit;
+            self.just_pressed |= bit;
+        }
+    }
+
+    /// Marks `action` as held down since an earlier tick.
+    pub fn hold(&mut self, action: Action) {
+        if let Some(bit) = action.bit() {
+            self.pressed |= bit;
+        }
+    }
+}
+
+/// Presses seen since the last simulation tick. Frames can run faster than the simulation, so a
+/// tap that starts and ends between two ticks would be lost otherwise.
+#[derive(Resource, Default)]
+pub struct BufferedPresses(PlayerInput);
+
+pub fn buffer_player_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut buffered: ResMut<BufferedPresses>,
+) {
+    for action in Action::PIECE {
+        if bindings.just_pressed(&keyboard_input, action) {
+            buffered.0.press(action);
+        }
+    }
+}
+
+/// Samples the keyboard for this tick and records it for the replay.
+pub fn read_player_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut buffered: ResMut<BufferedPresses>,
+    mut input: ResMut<PlayerInput>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    *input = std::mem::take(&mut buffered.0);
+    for action in Action::PIECE {
+        if bindings.pressed(&keyboard_input, action) {
+            input.hold(action);
+        }
+    }
+
+    recorder.push(*input);
+}
+
+/// Feeds the next tick of the loaded replay, ending the game once it runs out.
+pub fn read_replay_input(
+    mut input: ResMut<PlayerInput>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut ev_game_over: EventWriter<GameOverEvent>,
+) {
+    *input = playback.next().unwrap_or_else(|| {
+        ev_game_over.send(GameOverEvent);
+        PlayerInput::default()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_key_moves_to_the_action_it_is_bound_to() {
+        let mut bindings = KeyBindings::default();
+        bindings.toggle(Action::HardDrop, KeyCode::ArrowUp);
+        bindings.toggle(Action::RotateClockwise, KeyCode::ArrowUp);
+
+        assert_eq!(bindings.keys(Action::HardDrop), &[KeyCode::Space]);
+        assert_eq!(
+            bindings.keys(Action::RotateClockwise),
+            &[KeyCode::KeyZ, KeyCode::ArrowUp]
+        );
+
+        bindings.toggle(Action::RotateClockwise, KeyCode::KeyZ);
+        assert_eq!(bindings.keys(Action::RotateClockwise), &[KeyCode::ArrowUp]);
+    }
+
+    #[test]
+    fn only_piece_actions_reach_the_simulation() {
+        let mut input = PlayerInput::default();
+        for action in Action::ALL {
+            input.press(action);
+        }
+
+        for action in Action::ALL {
+            assert_eq!(
+                input.just_pressed(action),
+                Action::PIECE.contains(&action),
+                "{:?}",
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn bindings_survive_a_round_trip() {
+        let mut bindings = KeyBindings::default();
+        bindings.toggle(Action::Hold, KeyCode::ShiftLeft);
+        let saved = ron::to_string(&bindings).unwrap();
+
+        assert_eq!(ron::from_str::<KeyBindings>(&saved).unwrap(), bindings);
+    }
+}
@@ -1,22 +1,47 @@
 mod animation;
 mod collision;
+mod controls;
+mod editor;
+mod engine;
+mod frame_timer;
+mod fumen;
+mod garbage;
 mod ghost;
+mod gravity;
+mod input;
+mod modes;
 mod piece_actions;
 mod piece_builder;
+mod puzzle;
+mod records;
+mod replay;
+mod settings;
+#[cfg(feature = "audio")]
 mod sounds;
 mod stats;
 mod ui;
 mod user_actions;
 mod wall;
 
-use std::time::Duration;
-
 use animation::*;
 use bevy::prelude::*;
 use collision::*;
+use controls::*;
+use editor::*;
+use frame_timer::FrameTimer;
+use fumen::*;
+use garbage::*;
 use ghost::update_ghost_position;
+use gravity::*;
+use input::*;
+use modes::*;
 use piece_actions::*;
 use piece_builder::*;
+use puzzle::*;
+use records::*;
+use replay::*;
+use settings::*;
+#[cfg(feature = "audio")]
 use sounds::*;
 use stats::*;
 use ui::*;
@@ -32,6 +57,17 @@ pub enum GameState {
     Playing,
     Paused,
     GameOver,
+    /// Playing back a replay given with `--replay`.
+    Replay,
+    /// Setting up a board, hold and queue to play from.
+    Editor,
+    /// Rebinding the keys of each action.
+    Controls,
+}
+
+/// Whether the simulation should advance, either from the player or from a replay.
+pub fn in_game(game_state: Res<State<GameState>>) -> bool {
+    matches!(game_state.get(), GameState::Playing | GameState::Replay)
 }
 
 fn text_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
@@ -63,7 +99,6 @@ fn text_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                 font: bold_font.clone(),
                 font_size: 60.0,
                 color: Color::WHITE,
-                ..default()
             },
         )
         .with_justify(text_justification),
@@ -84,14 +119,13 @@ fn text_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                     font: reg_font.clone(),
                     font_size: 42.0,
                     color: Color::WHITE,
-                    ..default()
                 },
             )
             .with_justify(text_justification),
             transform: Transform::from_xyz(LEFT_GRID * 1.7, SCORE_Y - 40.0, 0.0),
             ..default()
         },
-        Score(0),
+        ScoreText,
     ));
 
     commands.spawn(Text2dBundle {
@@ -108,14 +142,65 @@ fn text_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                     font: reg_font.clone(),
                     font_size: 42.0,
                     color: Color::WHITE,
-                    ..default()
                 },
             )
             .with_justify(text_justification),
             transform: Transform::from_xyz(LEFT_GRID * 1.7, SCORE_Y - 120.0, 0.0),
             ..default()
         },
-        Level(1),
+        LevelText,
+    ));
+
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: bold_font.clone(),
+                    font_size: 28.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_justify(text_justification),
+            transform: Transform::from_xyz(LEFT_GRID * 1.7, SCORE_Y - 180.0, 0.0),
+            ..default()
+        },
+        ClearLabel(Timer::from_seconds(1.5, TimerMode::Once)),
+    ));
+
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: bold_font.clone(),
+                    font_size: 28.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_justify(text_justification),
+            transform: Transform::from_xyz(LEFT_GRID * 1.7, SCORE_Y + 50.0, 0.0),
+            ..default()
+        },
+        StreakText,
+    ));
+
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: reg_font.clone(),
+                    font_size: 22.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_justify(text_justification),
+            text_anchor: bevy::sprite::Anchor::TopCenter,
+            transform: Transform::from_xyz(RIGHT_GRID * 1.6, -190.0, 0.0),
+            ..default()
+        },
+        ModeText,
     ));
 }
 
@@ -207,7 +292,12 @@ fn pause_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
 #[derive(Component)]
 pub struct TitleMenu;
 
-fn title_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn title_menu_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    game_state: Res<State<GameState>>,
+    puzzles: Res<Puzzles>,
+) {
     let root = commands
         .spawn((
             NodeBundle {
@@ -223,7 +313,11 @@ fn title_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                     ..Default::default()
                 },
                 background_color: BackgroundColor(Color::DARK_GRAY),
-                visibility: Visibility::Visible,
+                visibility: if *game_state.get() == GameState::Title {
+                    Visibility::Visible
+                } else {
+                    Visibility::Hidden
+                },
                 ..Default::default()
             },
             TitleMenu,
@@ -235,6 +329,8 @@ fn title_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
             style: Style {
                 width: Val::Percent(100.0),
                 height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(20.0),
                 align_items: AlignItems::Center,
                 justify_content: JustifyContent::Center,
                 ..default()
@@ -258,44 +354,138 @@ fn title_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                 },
                 ..default()
             },));
-            parent
-                .spawn((
-                    ButtonBundle {
-                        style: Style {
-                            width: Val::Px(150.0),
-                            height: Val::Px(65.0),
-                            border: UiRect::all(Val::Px(5.0)),
-                            // horizontally center child text
-                            justify_content: JustifyContent::Center,
-                            // vertically center child text
-                            align_items: AlignItems::Center,
-                            ..default()
-                        },
-                        border_color: BorderColor(Color::BLACK),
-                        background_color: NORMAL_BUTTON.into(),
-                        ..default()
+            let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+            title_menu_row(parent, font.clone(), "Play", GameMode::Endless);
+            title_menu_row(
+                parent,
+                font.clone(),
+                "Sprint",
+                GameMode::Sprint { lines: 40 },
+            );
+            title_menu_row(
+                parent,
+                font.clone(),
+                "Ultra",
+                GameMode::Ultra { seconds: 120 },
+            );
+            title_menu_row(
+                parent,
+                font.clone(),
+                "Marathon",
+                GameMode::Marathon {
+                    start_level: 1,
+                    line_cap: Some(150),
+                },
+            );
+            title_menu_row(parent, font.clone(), "Master", GameMode::Master);
+            title_menu_row(
+                parent,
+                font.clone(),
+                "Dig",
+                GameMode::Dig {
+                    lines: 10,
+                    messiness: 100,
+                },
+            );
+            title_menu_row(parent, font.clone(), "Survival", GameMode::Survival);
+            if !puzzles.0.is_empty() {
+                title_menu_row(
+                    parent,
+                    font.clone(),
+                    "Puzzle",
+                    GameMode::Puzzle {
+                        index: 0,
+                        count: puzzles.0.len(),
                     },
-                    TitleButton,
-                ))
-                .with_children(|parent| {
-                    parent.spawn(TextBundle::from_section(
-                        "Play",
-                        TextStyle {
-                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                            font_size: 40.0,
-                            color: Color::rgb(0.9, 0.9, 0.9),
-                        },
-                    ));
-                });
+                );
+            }
+            title_menu_button(parent, font.clone(), "Editor", EditorButton);
+            title_menu_button(parent, font, "Controls", ControlsButton);
         })
         .id();
 
     commands.entity(root).add_child(button);
 }
 
+/// A button starting `mode`, followed by one cycling its option if it has any.
+fn title_menu_row(parent: &mut ChildBuilder, font: Handle<Font>, label: &str, mode: GameMode) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                column_gap: Val::Px(20.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            let mode_button = title_menu_button(parent, font.clone(), label, TitleButton(mode));
+            for (option, label) in mode.option_labels().iter().enumerate() {
+                title_menu_button(
+                    parent,
+                    font.clone(),
+                    label,
+                    ModeOptionButton {
+                        mode_button,
+                        option,
+                    },
+                );
+            }
+        });
+}
+
+fn title_menu_button(
+    parent: &mut ChildBuilder,
+    font: Handle<Font>,
+    label: &str,
+    button: impl Component,
+) -> Entity {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(200.0),
+                    height: Val::Px(65.0),
+                    border: UiRect::all(Val::Px(5.0)),
+                    // horizontally center child text
+                    justify_content: JustifyContent::Center,
+                    // vertically center child text
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_color: BorderColor(Color::BLACK),
+                background_color: NORMAL_BUTTON.into(),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font,
+                    font_size: 40.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            ));
+        })
+        .id()
+}
+
 #[derive(Component)]
 pub struct GameOverMenu;
 
+/// Heading of the results screen, "Game Over" unless the goal of the mode was reached.
+#[derive(Component)]
+pub struct GameOverTitle;
+
+/// Mode specific results, such as the time of a sprint.
+#[derive(Component)]
+pub struct ResultText;
+
+/// Shows the seed of the finished game so it can be replayed with `--seed`.
+#[derive(Component)]
+pub struct SeedText;
+
 fn game_over_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     let root = commands
         .spawn((
@@ -331,22 +521,25 @@ fn game_over_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
             ..default()
         })
         .with_children(|parent| {
-            parent.spawn((TextBundle {
-                text: Text::from_section(
-                    "Game Over",
-                    TextStyle {
-                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                        font_size: 60.0,
-                        color: Color::rgb(0.9, 0.9, 0.9),
+            parent.spawn((
+                TextBundle {
+                    text: Text::from_section(
+                        "Game Over",
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 60.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    ),
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        top: Val::Px(100.0),
+                        ..default()
                     },
-                ),
-                style: Style {
-                    position_type: PositionType::Absolute,
-                    top: Val::Px(100.0),
                     ..default()
                 },
-                ..default()
-            },));
+                GameOverTitle,
+            ));
             parent
                 .spawn((
                     ButtonBundle {
@@ -411,7 +604,7 @@ fn game_over_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                     },
                     ..default()
                 },
-                Score(0),
+                ScoreText,
             ));
             parent.spawn((TextBundle {
                 text: Text::from_section(
@@ -448,7 +641,64 @@ fn game_over_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                     },
                     ..default()
                 },
-                Level(0),
+                LevelText,
+            ));
+            parent.spawn((TextBundle {
+                text: Text::from_section(
+                    "Seed:",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 40.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                ),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(300.0),
+                    left: Val::Vw(44.0),
+                    ..default()
+                },
+                ..default()
+            },));
+            parent.spawn((
+                TextBundle {
+                    text: Text::from_section(
+                        "0",
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 40.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    ),
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Vw(53.0),
+                        top: Val::Px(300.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                SeedText,
+            ));
+            parent.spawn((
+                TextBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 32.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    ),
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Vw(44.0),
+                        top: Val::Px(360.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                ResultText,
             ));
         })
         .id();
@@ -462,29 +712,60 @@ pub struct DespawnOnRestart;
 fn game_over(
     mut next_state: ResMut<NextState<GameState>>,
     mut ev_game_over: EventReader<GameOverEvent>,
-    mut game_over_menu: Query<&mut Visibility, With<GameOverMenu>>,
 ) {
     if ev_game_over.read().next().is_some() {
         next_state.set(GameState::GameOver);
-        *game_over_menu.single_mut() = Visibility::Visible;
     }
     ev_game_over.clear();
 }
 
+/// The results text, kept apart from the other texts of the game over menu.
+type ResultTextOnly = (With<ResultText>, Without<SeedText>, Without<GameOverTitle>);
+
+#[allow(clippy::too_many_arguments)]
+fn show_game_over_menu(
+    randomizer: Res<PieceRandomizer>,
+    settings: Res<Settings>,
+    modes: ModeProgress,
+    records: Res<Records>,
+    mut game_over_menu: Query<&mut Visibility, With<GameOverMenu>>,
+    mut seed_text: Query<&mut Text, With<SeedText>>,
+    mut title_text: Query<&mut Text, (With<GameOverTitle>, Without<SeedText>)>,
+    mut result_text: Query<&mut Text, ResultTextOnly>,
+) {
+    *game_over_menu.single_mut() = Visibility::Visible;
+    for mut text in seed_text.iter_mut() {
+        text.sections[0].value = randomizer.seed.to_string();
+    }
+
+    let (title, summary) = results(&settings, &modes, &records);
+    for mut text in title_text.iter_mut() {
+        text.sections[0].value = title.clone();
+    }
+    for mut text in result_text.iter_mut() {
+        text.sections[0].value = summary.clone();
+    }
+}
+
 #[derive(Event)]
 pub struct RestartGameEvent;
 
+#[allow(clippy::too_many_arguments)]
 pub fn restart_game(
     game_state: Res<State<GameState>>,
     entities_to_despawn: Query<Entity, With<DespawnOnRestart>>,
-    music_controller: Query<&AudioSink, With<GameMusic>>,
     mut commands: Commands,
     mut ev_restart: EventReader<RestartGameEvent>,
     mut next_state: ResMut<NextState<GameState>>,
     mut placed_pieces: ResMut<PlacedPieces>,
     mut next_pieces: ResMut<NextPieces>,
+    mut engine: ResMut<Engine>,
+    mut randomizer: ResMut<PieceRandomizer>,
+    mut settings: ResMut<Settings>,
     mut game_over_menu: Query<&mut Visibility, (With<GameOverMenu>, Without<PauseMenu>)>,
-    mut drop_timer: ResMut<DropTimer>,
+    mut recorder: ResMut<ReplayRecorder>,
+    mut lock_delay: ResMut<LockDelay>,
+    playback: Option<ResMut<ReplayPlayback>>,
 ) {
     if ev_restart.read().next().is_some() {
         for row in placed_pieces.0.iter_mut() {
@@ -499,25 +780,37 @@ pub fn restart_game(
             commands.entity(entity).despawn();
         }
 
-        setup_pieces(commands, next_pieces);
+        setup_pieces(
+            commands,
+            next_pieces,
+            &mut placed_pieces,
+            &mut engine,
+            &mut randomizer,
+            &settings,
+        );
         toggle_menu(
             game_state,
             &mut next_state,
-            music_controller,
             &mut game_over_menu.single_mut(),
         );
 
-        drop_timer.0.set_duration(Duration::from_millis(500));
+        *recorder = ReplayRecorder::default();
+        recorder.fumen = settings.current_fumen().map(Fumen::encode);
+        // an imported board sets up the one game it was imported for, later ones start empty
+        if settings.fumen_imported {
+            settings.fumen = None;
+            settings.fumen_imported = false;
+        }
+        *lock_delay = LockDelay::default();
+        if let Some(mut playback) = playback {
+            playback.rewind();
+        }
     }
 
     ev_restart.clear();
 }
 
-fn setup(
-    next_pieces: ResMut<NextPieces>,
-    mut commands: Commands,
-    mut placed_pieces: ResMut<PlacedPieces>,
-) {
+fn scene_setup(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
 
     // build walls
@@ -550,29 +843,100 @@ fn setup(
             ..default()
         });
     }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn setup(
+    next_pieces: ResMut<NextPieces>,
+    settings: Res<Settings>,
+    mut engine: ResMut<Engine>,
+    mut randomizer: ResMut<PieceRandomizer>,
+    commands: Commands,
+    mut placed_pieces: ResMut<PlacedPieces>,
+    mut level: ResMut<Level>,
+    mut gravity: ResMut<Gravity>,
+    mut timing: ResMut<Timing>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    let mode = settings.ruleset.mode;
+    recorder.fumen = settings.current_fumen().map(Fumen::encode);
+    level.0 = mode.start_level();
+    gravity.set(mode.gravity(level.0));
+    *timing = settings.ruleset.timing();
 
     // build placed piece grid
-    for _ in 0..20 {
-        let mut row: Vec<Option<Entity>> = Vec::with_capacity(10);
-        row.resize(10, None);
+    for _ in 0..engine::VISIBLE_HEIGHT {
+        let mut row: Vec<Option<Entity>> = Vec::with_capacity(engine::BOARD_WIDTH);
+        row.resize(engine::BOARD_WIDTH, None);
         placed_pieces.0.push(row);
     }
 
-    setup_pieces(commands, next_pieces);
+    setup_pieces(
+        commands,
+        next_pieces,
+        &mut placed_pieces,
+        &mut engine,
+        &mut randomizer,
+        &settings,
+    );
 }
 
-pub fn setup_pieces(mut commands: Commands, mut next_pieces: ResMut<NextPieces>) {
-    let starting_piece_type = get_random_piece();
+/// Starts a new game with a fresh seed, on the board and queue of the puzzle being played, or on an
+/// imported fumen board, and builds the sprites for its active, ghost and next pieces.
+pub fn setup_pieces(
+    mut commands: Commands,
+    mut next_pieces: ResMut<NextPieces>,
+    placed_pieces: &mut PlacedPieces,
+    engine: &mut Engine,
+    randomizer: &mut PieceRandomizer,
+    settings: &Settings,
+) {
+    let system = settings.ruleset.rotation;
+    match settings.current_puzzle() {
+        Some(puzzle) => {
+            *randomizer = PieceRandomizer::fixed(&puzzle.queue);
+            engine.0 = puzzle.game();
+            build_board(&mut commands, &engine.board, placed_pieces);
+            if let Some(hold) = puzzle.hold {
+                build_held_piece(&mut commands, hold, system);
+            }
+        }
+        None => {
+            *randomizer = PieceRandomizer::new(settings.ruleset.randomizer, settings.game_seed());
+            match settings.current_fumen() {
+                Some(fumen) => {
+                    randomizer.deal_first(&fumen.pieces());
+                    engine.0 = fumen.game();
+                    build_board(&mut commands, &engine.board, placed_pieces);
+                    if let Some(hold) = fumen.hold {
+                        build_held_piece(&mut commands, hold, system);
+                    }
+                }
+                None => engine.0 = engine::Game::default(),
+            }
+        }
+    }
+    engine.rotation_system = system;
+    engine.kicks_180 = settings.ruleset.kicks_180;
+    for _ in 0..4 {
+        if let Some(piece) = randomizer.next() {
+            engine.push_next(piece);
+        }
+    }
+    engine.spawn_next();
 
+    let starting_piece = engine.active.unwrap();
     build_active_piece(
         &mut commands,
-        &starting_piece_type,
-        Vec3::new(0.0, 240.0, -1.0),
+        &starting_piece.kind.into(),
+        system,
+        cell_translation(starting_piece.x, starting_piece.y),
     );
 
     let ghost_piece_entities = build_piece(
         &mut commands,
-        &PieceType::Ghost(Box::new(starting_piece_type)),
+        &PieceType::Ghost(Box::new(starting_piece.kind.into())),
+        system,
         Vec3::new(0.0, BOTTOM_GRID, -0.5),
     );
 
@@ -580,10 +944,13 @@ pub fn setup_pieces(mut commands: Commands, mut next_pieces: ResMut<NextPieces>)
         commands.entity(ghost_entity).insert(Ghost);
     }
 
-    for _ in 0..3 {
-        let new_piece = get_random_piece();
-
-        let entities = build_piece(&mut commands, &new_piece, Vec3::new(0.0, 0.0, -1.0));
+    for &new_piece in engine.next.iter() {
+        let entities = build_piece(
+            &mut commands,
+            &new_piece.into(),
+            system,
+            Vec3::new(0.0, 0.0, -1.0),
+        );
         // only push the parent which is always the first entiy
         next_pieces.0.push(entities[0]);
     }
@@ -592,83 +959,213 @@ pub fn setup_pieces(mut commands: Commands, mut next_pieces: ResMut<NextPieces>)
 #[derive(Event)]
 pub struct GameOverEvent;
 
-fn main() {
-    App::new()
-        .init_state::<GameState>()
-        .insert_resource(DropTimer(Timer::from_seconds(0.6, TimerMode::Repeating)))
-        .insert_resource(MovementTimer(Timer::from_seconds(0.1, TimerMode::Once)))
-        .insert_resource(UserDropTimer(Timer::from_seconds(0.1, TimerMode::Once)))
-        .insert_resource(PlacedPieces(Vec::new()))
-        .insert_resource(NextPieces(Vec::new()))
-        .insert_resource(GracePeriodTimer(Timer::from_seconds(0.5, TimerMode::Once)))
-        .insert_resource(AttemptingPlaceAnimationTimer(Timer::from_seconds(
+/// Rules, timing and the sprites that mirror the board. Needs nothing from `DefaultPlugins`, so
+/// whole games can also run headless.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameState>()
+            .insert_resource(Time::<Fixed>::from_hz(60.0))
+            .init_resource::<Gravity>()
+            .insert_resource(SoftDropTimer(FrameTimer::new(6, TimerMode::Once)))
+            .init_resource::<AutoShift>()
+            .insert_resource(UserDropTimer(FrameTimer::new(6, TimerMode::Once)))
+            .insert_resource(PlacedPieces(Vec::new()))
+            .insert_resource(NextPieces(Vec::new()))
+            .init_resource::<Engine>()
+            .init_resource::<Settings>()
+            .init_resource::<PlayerInput>()
+            .init_resource::<KeyBindings>()
+            .init_resource::<BufferedPresses>()
+            .init_resource::<ReplayRecorder>()
+            .init_resource::<Score>()
+            .init_resource::<Level>()
+            .init_resource::<PerfectClears>()
+            .init_resource::<GameClock>()
+            .init_resource::<SprintProgress>()
+            .init_resource::<UltraProgress>()
+            .init_resource::<MarathonProgress>()
+            .init_resource::<MasterProgress>()
+            .init_resource::<DigProgress>()
+            .init_resource::<SurvivalProgress>()
+            .init_resource::<PuzzleProgress>()
+            .init_resource::<Puzzles>()
+            .init_resource::<Garbage>()
+            .init_resource::<Timing>()
+            .init_resource::<Records>()
+            .insert_resource(PieceRandomizer::new(default(), 0))
+            .init_resource::<LockDelay>()
+            //.insert_resource(PlaceGracePeriod(Timer::from_seconds(0.25, TimerMode::Once)))
+            .add_event::<PiecePlacedEvent>()
+            .add_event::<ClearEvent>()
+            .add_event::<TSpinEvent>()
+            .add_event::<MoveEvent>()
+            .add_event::<RotateEvent>()
+            .add_event::<LevelUpEvent>()
+            .add_event::<HoldPieceEvent>()
+            .add_event::<GameOverEvent>()
+            .add_event::<GoalReachedEvent>()
+            .add_event::<AttemptPlaceEvent>()
+            .add_event::<RestartGameEvent>()
+            .add_event::<DropPieceEvent>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, reset_mode_progress)
+            .add_systems(
+                PreUpdate,
+                buffer_player_input.after(bevy::input::InputSystem),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    (
+                        read_player_input.run_if(in_state(GameState::Playing)),
+                        read_replay_input.run_if(in_state(GameState::Replay)),
+                    ),
+                    tick_game_clock,
+                    user_rotate_active,
+                    user_move_actives,
+                    shift_active_down,
+                    check_collision,
+                    game_over,
+                    try_to_place_piece,
+                    score,
+                    place_piece,
+                    clear_rows,
+                    (keep_garbage_height, rise_garbage),
+                    spawn_next_piece,
+                    level,
+                    (sprint, ultra, marathon, master, dig, puzzle).chain(),
+                    finish_game,
+                    hold_piece,
+                    save_replay.run_if(in_state(GameState::Playing)),
+                )
+                    .chain()
+                    .run_if(in_game),
+            );
+    }
+}
+
+/// Window, menus, text, sound and animation on top of [`SimulationPlugin`].
+pub struct PresentationPlugin;
+
+impl Plugin for PresentationPlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(feature = "audio")]
+        app.add_plugins(SoundPlugin);
+
+        app.insert_resource(AttemptingPlaceAnimationTimer(Timer::from_seconds(
             0.1,
             TimerMode::Repeating,
         )))
-        .insert_resource(ClearingAnimationTimer(Timer::from_seconds(
-            0.05,
-            TimerMode::Repeating,
-        )))
         .insert_resource(FlashingAnimationTimer(Timer::from_seconds(
             0.1,
             TimerMode::Repeating,
         )))
-        //.insert_resource(PlaceGracePeriod(Timer::from_seconds(0.25, TimerMode::Once)))
-        .add_event::<PiecePlacedEvent>()
-        .add_event::<CollisionEvent>()
-        .add_event::<ClearEvent>()
-        .add_event::<MoveEvent>()
-        .add_event::<RotateEvent>()
-        .add_event::<LevelUpEvent>()
-        .add_event::<HoldPieceEvent>()
-        .add_event::<GameOverEvent>()
-        .add_event::<AttemptPlaceEvent>()
+        .init_resource::<Editor>()
+        .init_resource::<Rebinding>()
         .add_event::<PauseGameEvent>()
-        .add_event::<RestartGameEvent>()
-        .add_event::<DropPieceEvent>()
-        .add_plugins(DefaultPlugins)
         .add_systems(
             Startup,
             (
-                setup,
+                scene_setup,
                 text_setup,
-                sound_setup,
                 pause_setup,
                 game_over_setup,
                 title_menu_setup,
+                editor_setup,
+                controls_menu_setup,
             ),
         )
         .add_systems(PreUpdate, position_next_pieces)
+        .add_systems(OnEnter(GameState::GameOver), show_game_over_menu)
+        .add_systems(OnEnter(GameState::Editor), enter_editor)
+        .add_systems(OnExit(GameState::Editor), exit_editor)
+        .add_systems(OnEnter(GameState::Controls), enter_controls)
+        .add_systems(OnExit(GameState::Controls), exit_controls)
         .add_systems(
             Update,
             (
+                pause_game.run_if(
+                    not(in_state(GameState::GameOver))
+                        .and_then(not(in_state(GameState::Replay)))
+                        .and_then(not(in_state(GameState::Editor)))
+                        .and_then(not(in_state(GameState::Controls))),
+                ),
+                pause_button.run_if(in_state(GameState::Paused)),
+                restart_button.run_if(not(in_state(GameState::Playing))),
                 (
-                    user_rotate_active,
-                    check_collision,
-                    user_move_actives,
-                    shift_active_down,
-                    game_over,
-                    try_to_place_piece,
-                    placing_piece_animation,
-                    score,
-                    place_piece_animation,
-                    place_piece,
-                    clear_rows,
-                    level,
+                    title_button,
+                    mode_option_button,
+                    editor_button,
+                    controls_button,
                 )
+                    .run_if(in_state(GameState::Title)),
+                (controls_menu_buttons, rebind_key, update_binding_text)
                     .chain()
-                    .run_if(in_state(GameState::Playing)),
-                pause_game.run_if(not(in_state(GameState::GameOver))),
-                pause_music,
-                pause_button.run_if(in_state(GameState::Paused)),
-                restart_button.run_if(not(in_state(GameState::Playing))),
-                title_button.run_if(in_state(GameState::Title)),
-                user_restart,
+                    .run_if(in_state(GameState::Controls)),
+                (paint_cells, edit_setup, update_editor_text)
+                    .chain()
+                    .run_if(in_state(GameState::Editor)),
+                user_restart.run_if(
+                    not(in_state(GameState::Editor)).and_then(not(in_state(GameState::Controls))),
+                ),
                 restart_game,
-                sound_effects,
+                export_fumen.run_if(
+                    not(in_state(GameState::Title)).and_then(not(in_state(GameState::Controls))),
+                ),
+                import_fumen
+                    .run_if(in_state(GameState::Playing).or_else(in_state(GameState::GameOver))),
                 button_system,
+                update_stats_text,
+                update_mode_text,
+                show_clear_label,
             ),
         )
-        .add_systems(PostUpdate, (hold_piece, update_ghost_position))
+        .add_systems(
+            PostUpdate,
+            (
+                (sync_active_piece, update_ghost_position).chain(),
+                (placing_piece_animation, place_piece_animation).run_if(in_game),
+            ),
+        );
+    }
+}
+
+fn main() {
+    let mut settings = Settings::from_args();
+    let playback = settings.replay.as_ref().map(|path| {
+        let replay = Replay::load(path).unwrap_or_else(|err| {
+            eprintln!("could not load replay {}: {}", path.display(), err);
+            std::process::exit(1);
+        });
+        ReplayPlayback::new(replay)
+    });
+
+    let puzzles = Puzzles::load();
+    let mut app = App::new();
+    if let Some(playback) = playback {
+        settings.seed = Some(playback.replay.seed);
+        settings.ruleset = playback.replay.ruleset;
+        if let GameMode::Puzzle { index, .. } = settings.ruleset.mode {
+            settings.puzzle = puzzles.0.get(index).cloned();
+        }
+        settings.fumen = playback
+            .replay
+            .fumen
+            .as_ref()
+            .and_then(|fumen| Fumen::decode(fumen).ok());
+        app.insert_state(GameState::Replay)
+            .insert_resource(playback);
+    }
+
+    app.insert_resource(settings)
+        .insert_resource(Records::load())
+        .insert_resource(KeyBindings::load())
+        .insert_resource(puzzles)
+        .add_plugins((DefaultPlugins, SimulationPlugin, PresentationPlugin))
         .run();
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,712 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    endless_gravity, engine::Spin, guideline_gravity, ClearEvent, Engine, GameOverEvent, GameState,
+    PiecePlacedEvent, PuzzleGoal, Records, RestartGameEvent, Score, Settings, SprintRecord,
+    TWENTY_G,
+};
+
+/// What a game is played for, and what ends it besides topping out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Play until topping out, speeding up every 10 lines.
+    #[default]
+    Endless,
+    /// Clear `lines` lines as fast as possible at a fixed speed.
+    Sprint { lines: u32 },
+    /// Score as much as possible in `seconds` at a fixed speed.
+    Ultra { seconds: u32 },
+    /// Level up every 10 lines along the guideline gravity curve, until `line_cap` lines if set.
+    Marathon {
+        start_level: u32,
+        line_cap: Option<u32>,
+    },
+    /// 20G from the start, with delays that shorten every 100 levels up to level 999.
+    Master,
+    /// Dig out `lines` rows of garbage, kept topped up to [`crate::GARBAGE_HEIGHT`] rows. The hole
+    /// of each row moves away from the one below with a `messiness` percent chance.
+    Dig { lines: u32, messiness: u32 },
+    /// Last as long as possible while garbage rises from below, faster every minute.
+    Survival,
+    /// Reach the goal of puzzle `index` of the `count` in `assets/puzzles` with its fixed queue.
+    Puzzle { index: usize, count: usize },
+}
+
+/// Line targets the title menu cycles through for sprints.
+const SPRINT_LINES: [u32; 4] = [20, 40, 100, 1000];
+
+/// Durations the title menu cycles through for ultras.
+const ULTRA_SECONDS: [u32; 4] = [60, 120, 180, 300];
+
+/// Levels the title menu cycles through for marathons.
+const MARATHON_START_LEVELS: [u32; 4] = [1, 5, 10, 15];
+
+/// Line caps the title menu cycles through for marathons, `None` for endless.
+const MARATHON_LINE_CAPS: [Option<u32>; 3] = [Some(150), Some(200), None];
+
+/// Garbage targets the title menu cycles through for digs.
+const DIG_LINES: [u32; 3] = [10, 18, 100];
+
+/// Messiness the title menu cycles through for digs.
+const DIG_MESSINESS: [u32; 4] = [0, 25, 50, 100];
+
+/// The choice after `current`, wrapping around.
+fn next_choice<T: Copy + PartialEq>(choices: &[T], current: T) -> T {
+    let next = choices
+        .iter()
+        .position(|&choice| choice == current)
+        .map_or(0, |i| (i + 1) % choices.len());
+    choices[next]
+}
+
+impl GameMode {
+    /// The settings a mode is played with, such as the line target of a sprint.
+    pub fn option_labels(&self) -> Vec<String> {
+        match self {
+            GameMode::Endless | GameMode::Master | GameMode::Survival => vec![],
+            GameMode::Sprint { lines } => vec![format!("{} Lines", lines)],
+            GameMode::Ultra { seconds } => vec![format!("{}:{:02}", seconds / 60, seconds % 60)],
+            GameMode::Marathon {
+                start_level,
+                line_cap,
+            } => vec![
+                format!("Level {}", start_level),
+                match line_cap {
+                    Some(lines) => format!("{} Lines", lines),
+                    None => "Endless".to_string(),
+                },
+            ],
+            GameMode::Dig { lines, messiness } => {
+                vec![format!("{} Lines", lines), format!("{}% Messy", messiness)]
+            }
+            GameMode::Puzzle { index, count } => vec![format!("{}/{}", index + 1, count)],
+        }
+    }
+
+    /// Moves setting `option` of [`GameMode::option_labels`] on to its next choice.
+    pub fn cycle_option(&mut self, option: usize) {
+        match (self, option) {
+            (GameMode::Sprint { lines }, 0) => *lines = next_choice(&SPRINT_LINES, *lines),
+            (GameMode::Ultra { seconds }, 0) => *seconds = next_choice(&ULTRA_SECONDS, *seconds),
+            (GameMode::Marathon { start_level, .. }, 0) => {
+                *start_level = next_choice(&MARATHON_START_LEVELS, *start_level)
+            }
+            (GameMode::Marathon { line_cap, .. }, 1) => {
+                *line_cap = next_choice(&MARATHON_LINE_CAPS, *line_cap)
+            }
+            (GameMode::Dig { lines, .. }, 0) => *lines = next_choice(&DIG_LINES, *lines),
+            (GameMode::Dig { messiness, .. }, 1) => {
+                *messiness = next_choice(&DIG_MESSINESS, *messiness)
+            }
+            (GameMode::Puzzle { index, count }, 0) => *index = (*index + 1) % (*count).max(1),
+            _ => {}
+        }
+    }
+
+    /// Whether clearing lines raises the level and with it the gravity.
+    pub fn levels_up(&self) -> bool {
+        matches!(self, GameMode::Endless | GameMode::Marathon { .. })
+    }
+
+    pub fn start_level(&self) -> u32 {
+        match self {
+            GameMode::Marathon { start_level, .. } => *start_level,
+            _ => 1,
+        }
+    }
+
+    /// Gravity at `level`, in [`crate::gravity::SUBROWS`] per tick.
+    pub fn gravity(&self, level: u32) -> u32 {
+        match self {
+            GameMode::Marathon { .. } => guideline_gravity(level),
+            GameMode::Master => TWENTY_G,
+            _ => endless_gravity(level),
+        }
+    }
+}
+
+/// Delays around locking a piece, in ticks.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timing {
+    /// Entry delay between a piece locking, or its rows clearing, and the next piece spawning.
+    pub are: u32,
+    /// Ticks cleared rows take to disappear.
+    pub line_clear: u32,
+    /// Ticks a piece can rest on something before it locks.
+    pub lock_delay: u32,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        crate::Ruleset::default().timing()
+    }
+}
+
+/// Timing of each section of 100 levels in [`GameMode::Master`]. Later sections keep the last.
+const MASTER_TIMING: [Timing; 6] = [
+    Timing {
+        are: 18,
+        line_clear: 12,
+        lock_delay: 30,
+    },
+    Timing {
+        are: 14,
+        line_clear: 6,
+        lock_delay: 26,
+    },
+    Timing {
+        are: 14,
+        line_clear: 6,
+        lock_delay: 22,
+    },
+    Timing {
+        are: 8,
+        line_clear: 6,
+        lock_delay: 18,
+    },
+    Timing {
+        are: 7,
+        line_clear: 5,
+        lock_delay: 15,
+    },
+    Timing {
+        are: 6,
+        line_clear: 4,
+        lock_delay: 15,
+    },
+];
+
+/// Last level of [`GameMode::Master`], which finishes the game.
+const MASTER_MAX_LEVEL: u32 = 999;
+
+pub fn master_timing(level: u32) -> Timing {
+    MASTER_TIMING[(level as usize / 100).min(MASTER_TIMING.len() - 1)]
+}
+
+/// Simulation ticks since the game started.
+#[derive(Resource, Default)]
+pub struct GameClock(pub u32);
+
+/// The goal of the mode was reached. Ends the game like topping out does, but counts as a finish.
+#[derive(Event)]
+pub struct GoalReachedEvent;
+
+#[derive(Resource, Default)]
+pub struct SprintProgress {
+    pub lines: u32,
+    /// Ticks at which every 10th line was cleared.
+    pub splits: Vec<u32>,
+    pub finished: bool,
+    /// Personal best before this run, filled in when it finishes.
+    pub best: Option<SprintRecord>,
+}
+
+#[derive(Resource, Default)]
+pub struct UltraProgress {
+    pub finished: bool,
+    /// Place of the final score in the high score table, if it made it.
+    pub place: Option<usize>,
+}
+
+#[derive(Resource, Default)]
+pub struct MarathonProgress {
+    pub lines: u32,
+    pub finished: bool,
+}
+
+/// Level of [`GameMode::Master`], which counts pieces and lines rather than following [`crate::Level`].
+#[derive(Resource, Default)]
+pub struct MasterProgress {
+    pub level: u32,
+    pub finished: bool,
+}
+
+/// Garbage rows of [`GameMode::Dig`] pushed in and dug out so far.
+#[derive(Resource, Default)]
+pub struct DigProgress {
+    pub given: u32,
+    pub dug: u32,
+    pub finished: bool,
+}
+
+/// Garbage of [`GameMode::Survival`] risen so far, and ticks since the last row.
+#[derive(Resource, Default)]
+pub struct SurvivalProgress {
+    pub rows: u32,
+    pub waited: u32,
+}
+
+/// Lines cleared in [`GameMode::Puzzle`], and whether the puzzle was solved or its queue ran out.
+#[derive(Resource, Default)]
+pub struct PuzzleProgress {
+    pub lines: u32,
+    pub solved: bool,
+    pub failed: bool,
+}
+
+/// Ticks as `m:ss.mmm`.
+pub fn format_ticks(ticks: u32) -> String {
+    let millis = ticks as u64 * 1000 / 60;
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Difference between two times as `+m:ss.mmm` or `-m:ss.mmm`.
+pub fn format_diff(ticks: u32, best: u32) -> String {
+    if ticks < best {
+        format!("-{}", format_ticks(best - ticks))
+    } else {
+        format!("+{}", format_ticks(ticks - best))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn reset_mode_progress(
+    mut clock: ResMut<GameClock>,
+    mut sprint: ResMut<SprintProgress>,
+    mut ultra: ResMut<UltraProgress>,
+    mut marathon: ResMut<MarathonProgress>,
+    mut master: ResMut<MasterProgress>,
+    mut dig: ResMut<DigProgress>,
+    mut survival: ResMut<SurvivalProgress>,
+    mut puzzle: ResMut<PuzzleProgress>,
+    mut ev_restart: EventReader<RestartGameEvent>,
+) {
+    if ev_restart.read().next().is_some() {
+        *clock = GameClock::default();
+        *sprint = SprintProgress::default();
+        *ultra = UltraProgress::default();
+        *marathon = MarathonProgress::default();
+        *master = MasterProgress::default();
+        *dig = DigProgress::default();
+        *survival = SurvivalProgress::default();
+        *puzzle = PuzzleProgress::default();
+    }
+}
+
+pub fn tick_game_clock(mut clock: ResMut<GameClock>) {
+    clock.0 += 1;
+}
+
+pub fn sprint(
+    game_state: Res<State<GameState>>,
+    settings: Res<Settings>,
+    clock: Res<GameClock>,
+    mut records: ResMut<Records>,
+    mut progress: ResMut<SprintProgress>,
+    mut ev_clear: EventReader<ClearEvent>,
+    mut ev_goal: EventWriter<GoalReachedEvent>,
+) {
+    let target = match settings.ruleset.mode {
+        GameMode::Sprint { lines } => lines,
+        _ => return,
+    };
+    if progress.finished {
+        return;
+    }
+
+    for ev in ev_clear.read() {
+        let lines = progress.lines + ev.rows.len() as u32;
+        for _ in progress.lines / 10..lines / 10 {
+            progress.splits.push(clock.0);
+        }
+        progress.lines = lines;
+    }
+    if progress.lines < target {
+        return;
+    }
+
+    progress.finished = true;
+    progress.best = records.sprint.get(&target).cloned();
+    ev_goal.send(GoalReachedEvent);
+
+    // only games actually played count as records
+    if *game_state.get() != GameState::Playing {
+        return;
+    }
+    if progress
+        .best
+        .as_ref()
+        .is_none_or(|best| clock.0 < best.ticks)
+    {
+        records.sprint.insert(
+            target,
+            SprintRecord {
+                ticks: clock.0,
+                splits: progress.splits.clone(),
+            },
+        );
+        records.save();
+    }
+}
+
+pub fn ultra(
+    game_state: Res<State<GameState>>,
+    settings: Res<Settings>,
+    clock: Res<GameClock>,
+    score: Res<Score>,
+    mut records: ResMut<Records>,
+    mut progress: ResMut<UltraProgress>,
+    mut ev_goal: EventWriter<GoalReachedEvent>,
+) {
+    let seconds = match settings.ruleset.mode {
+        GameMode::Ultra { seconds } => seconds,
+        _ => return,
+    };
+    if progress.finished || clock.0 < seconds * 60 {
+        return;
+    }
+
+    progress.finished = true;
+    ev_goal.send(GoalReachedEvent);
+
+    // only games actually played count as records
+    if *game_state.get() != GameState::Playing {
+        return;
+    }
+    progress.place = records.add_ultra_score(seconds, score.0);
+    if progress.place.is_some() {
+        records.save();
+    }
+}
+
+pub fn marathon(
+    settings: Res<Settings>,
+    mut progress: ResMut<MarathonProgress>,
+    mut ev_clear: EventReader<ClearEvent>,
+    mut ev_goal: EventWriter<GoalReachedEvent>,
+) {
+    let line_cap = match settings.ruleset.mode {
+        GameMode::Marathon { line_cap, .. } => line_cap,
+        _ => return,
+    };
+    if progress.finished {
+        return;
+    }
+
+    for ev in ev_clear.read() {
+        progress.lines += ev.rows.len() as u32;
+    }
+    if line_cap.is_some_and(|line_cap| progress.lines >= line_cap) {
+        progress.finished = true;
+        ev_goal.send(GoalReachedEvent);
+    }
+}
+
+pub fn dig(
+    settings: Res<Settings>,
+    mut progress: ResMut<DigProgress>,
+    mut ev_clear: EventReader<ClearEvent>,
+    mut ev_goal: EventWriter<GoalReachedEvent>,
+) {
+    let lines = match settings.ruleset.mode {
+        GameMode::Dig { lines, .. } => lines,
+        _ => return,
+    };
+    if progress.finished {
+        return;
+    }
+
+    for ev in ev_clear.read() {
+        progress.dug += ev.garbage as u32;
+    }
+    if progress.dug >= lines {
+        progress.finished = true;
+        ev_goal.send(GoalReachedEvent);
+    }
+}
+
+/// Every piece raises the level by one, except on the last level of a section, and every cleared
+/// line by one more.
+pub fn master(
+    settings: Res<Settings>,
+    mut progress: ResMut<MasterProgress>,
+    mut timing: ResMut<Timing>,
+    mut ev_piece_placed: EventReader<PiecePlacedEvent>,
+    mut ev_clear: EventReader<ClearEvent>,
+    mut ev_goal: EventWriter<GoalReachedEvent>,
+) {
+    if settings.ruleset.mode != GameMode::Master || progress.finished {
+        return;
+    }
+
+    let mut level = progress.level;
+    for _ in ev_piece_placed.read() {
+        if level % 100 != 99 {
+            level += 1;
+        }
+    }
+    for ev in ev_clear.read() {
+        level += ev.rows.len() as u32;
+    }
+    let level = level.min(MASTER_MAX_LEVEL);
+    if level == progress.level {
+        return;
+    }
+
+    progress.level = level;
+    *timing = master_timing(level);
+    if level == MASTER_MAX_LEVEL {
+        progress.finished = true;
+        ev_goal.send(GoalReachedEvent);
+    }
+}
+
+/// Solves the puzzle when a clear reaches its goal, and fails it when the queue runs out first.
+pub fn puzzle(
+    settings: Res<Settings>,
+    engine: Res<Engine>,
+    mut progress: ResMut<PuzzleProgress>,
+    mut ev_clear: EventReader<ClearEvent>,
+    mut ev_goal: EventWriter<GoalReachedEvent>,
+    mut ev_game_over: EventWriter<GameOverEvent>,
+) {
+    let goal = match settings.current_puzzle() {
+        Some(puzzle) => puzzle.goal,
+        None => return,
+    };
+    if progress.solved || progress.failed {
+        return;
+    }
+
+    for ev in ev_clear.read() {
+        progress.lines += ev.rows.len() as u32;
+        progress.solved |= match goal {
+            PuzzleGoal::Lines(lines) => progress.lines >= lines,
+            PuzzleGoal::TSpin(lines) => ev.spin == Some(Spin::Full) && ev.rows.len() == lines,
+            PuzzleGoal::PerfectClear => ev.perfect,
+        };
+    }
+    if progress.solved {
+        ev_goal.send(GoalReachedEvent);
+        return;
+    }
+
+    let out_of_pieces =
+        engine.active.is_none() && engine.next.is_empty() && engine.clearing_rows().is_empty();
+    if out_of_pieces && !engine.is_game_over() {
+        progress.failed = true;
+        ev_game_over.send(GameOverEvent);
+    }
+}
+
+pub fn finish_game(
+    mut next_state: ResMut<NextState<GameState>>,
+    mut ev_goal: EventReader<GoalReachedEvent>,
+) {
+    if ev_goal.read().next().is_some() {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+/// Text showing the clock and progress of the current mode.
+#[derive(Component)]
+pub struct ModeText;
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_mode_text(
+    settings: Res<Settings>,
+    records: Res<Records>,
+    clock: Res<GameClock>,
+    progress: Res<SprintProgress>,
+    marathon: Res<MarathonProgress>,
+    master: Res<MasterProgress>,
+    dig: Res<DigProgress>,
+    survival: Res<SurvivalProgress>,
+    score: Res<Score>,
+    mut mode_text: Query<&mut Text, With<ModeText>>,
+) {
+    if !clock.is_changed()
+        && !progress.is_changed()
+        && !marathon.is_changed()
+        && !master.is_changed()
+        && !dig.is_changed()
+        && !survival.is_changed()
+        && !score.is_changed()
+        && !settings.is_changed()
+    {
+        return;
+    }
+
+    let value = match settings.ruleset.mode {
+        GameMode::Endless => String::new(),
+        GameMode::Sprint { lines } => {
+            let best = records.sprint.get(&lines);
+            let mut value = format!(
+                "TIME\n{}\nLINES {}/{}",
+                format_ticks(clock.0),
+                progress.lines.min(lines),
+                lines
+            );
+            let shown = progress.splits.len().saturating_sub(3);
+            for (i, &split) in progress.splits.iter().enumerate().skip(shown) {
+                value += &format!("\n{:>4} {}", (i + 1) * 10, format_ticks(split));
+                if let Some(&best_split) = best.and_then(|best| best.splits.get(i)) {
+                    value += &format!(" {}", format_diff(split, best_split));
+                }
+            }
+            value
+        }
+        GameMode::Ultra { seconds } => {
+            let left = (seconds * 60).saturating_sub(clock.0);
+            let mut value = format!("TIME LEFT\n{}", format_ticks(left));
+            if let Some(&best) = records
+                .ultra
+                .get(&seconds)
+                .and_then(|scores| scores.first())
+            {
+                value += &format!("\nBEST {}", best);
+                if score.0 > best {
+                    value += " (NEW)";
+                }
+            }
+            value
+        }
+        GameMode::Marathon { line_cap, .. } => match line_cap {
+            Some(line_cap) => format!("LINES\n{}/{}", marathon.lines.min(line_cap), line_cap),
+            None => format!("LINES\n{}", marathon.lines),
+        },
+        GameMode::Master => format!(
+            "LEVEL\n{}/{}",
+            master.level,
+            ((master.level / 100 + 1) * 100).min(MASTER_MAX_LEVEL)
+        ),
+        GameMode::Dig { lines, .. } => {
+            format!("GARBAGE\n{}", lines.saturating_sub(dig.dug))
+        }
+        GameMode::Survival => format!("GARBAGE\n{}", survival.rows),
+        GameMode::Puzzle { .. } => settings.current_puzzle().map_or(String::new(), |puzzle| {
+            format!("{}\n{}", puzzle.name.to_uppercase(), puzzle.goal.describe())
+        }),
+    };
+    for mut text in mode_text.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+/// The clock and the progress of every mode, for systems that report on whichever is played.
+#[derive(SystemParam)]
+pub struct ModeProgress<'w> {
+    pub clock: Res<'w, GameClock>,
+    pub sprint: Res<'w, SprintProgress>,
+    pub ultra: Res<'w, UltraProgress>,
+    pub marathon: Res<'w, MarathonProgress>,
+    pub master: Res<'w, MasterProgress>,
+    pub dig: Res<'w, DigProgress>,
+    pub survival: Res<'w, SurvivalProgress>,
+    pub puzzle: Res<'w, PuzzleProgress>,
+}
+
+/// Title and summary of the results screen for a game that just ended.
+pub fn results(settings: &Settings, modes: &ModeProgress, records: &Records) -> (String, String) {
+    let ModeProgress {
+        clock,
+        sprint: progress,
+        ultra,
+        marathon,
+        master,
+        dig,
+        survival,
+        puzzle,
+    } = modes;
+    if let Some(current) = settings.current_puzzle() {
+        let title = if puzzle.solved {
+            "Puzzle Solved"
+        } else {
+            "Puzzle Failed"
+        };
+        return (
+            title.to_string(),
+            format!("{}\n{}", current.name, current.goal.describe()),
+        );
+    }
+
+    match settings.ruleset.mode {
+        GameMode::Endless | GameMode::Puzzle { .. } => ("Game Over".to_string(), String::new()),
+        GameMode::Sprint { lines } if !progress.finished => (
+            "Game Over".to_string(),
+            format!("Lines: {}/{}", progress.lines, lines),
+        ),
+        GameMode::Sprint { .. } => {
+            let time = format!("Time: {}", format_ticks(clock.0));
+            let summary = match &progress.best {
+                Some(best) if clock.0 < best.ticks => format!(
+                    "{}\nNew personal best! ({})",
+                    time,
+                    format_diff(clock.0, best.ticks)
+                ),
+                Some(best) => format!(
+                    "{}\nPersonal best: {} ({})",
+                    time,
+                    format_ticks(best.ticks),
+                    format_diff(clock.0, best.ticks)
+                ),
+                None => format!("{}\nNew personal best!", time),
+            };
+            ("Sprint Complete".to_string(), summary)
+        }
+        GameMode::Ultra { .. } if !ultra.finished => ("Game Over".to_string(), String::new()),
+        GameMode::Ultra { seconds } => {
+            let mut summary = "High scores:".to_string();
+            let scores = records.ultra.get(&seconds).map_or(&[][..], |scores| scores);
+            for (i, score) in scores.iter().enumerate().take(5) {
+                summary += &format!("\n{}. {}", i + 1, score);
+                if ultra.place == Some(i) {
+                    summary += "  < you";
+                }
+            }
+            ("Time Up".to_string(), summary)
+        }
+        GameMode::Marathon { .. } => {
+            let title = if marathon.finished {
+                "Marathon Complete"
+            } else {
+                "Game Over"
+            };
+            (
+                title.to_string(),
+                format!("Lines: {}\nTime: {}", marathon.lines, format_ticks(clock.0)),
+            )
+        }
+        GameMode::Master => {
+            let title = if master.finished {
+                "Master Complete"
+            } else {
+                "Game Over"
+            };
+            (
+                title.to_string(),
+                format!("Level: {}\nTime: {}", master.level, format_ticks(clock.0)),
+            )
+        }
+        GameMode::Dig { lines, .. } => {
+            let title = if dig.finished {
+                "Dig Complete"
+            } else {
+                "Game Over"
+            };
+            (
+                title.to_string(),
+                format!(
+                    "Garbage: {}/{}\nTime: {}",
+                    dig.dug.min(lines),
+                    lines,
+                    format_ticks(clock.0)
+                ),
+            )
+        }
+        GameMode::Survival => (
+            "Game Over".to_string(),
+            format!(
+                "Survived: {}\nGarbage: {}",
+                format_ticks(clock.0),
+                survival.rows
+            ),
+        ),
+    }
+}
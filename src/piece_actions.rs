@@ -1,27 +1,24 @@
-use std::collections::HashSet;
-
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 
 use crate::{
-    build_piece, get_random_piece, Active, ClearEvent, Collision, CollisionEvent, DropPieceEvent,
-    GameOverEvent, Hold, PieceType, Placed, RestartGameEvent, RotateEvent, BOTTOM_GRID, LEFT_GRID,
-    RIGHT_GRID, SQUARE_SIZE, TOP_GRID,
+    build_piece,
+    engine::{
+        Board, Game, GameEvent, Input, PieceKind, RotationState, RotationSystem, BOARD_WIDTH,
+        VISIBLE_HEIGHT,
+    },
+    piece_part_translations, Action, Active, ClearEvent, DropPieceEvent, GameOverEvent, Gravity,
+    Hold, PiecePartBundle, PieceRandomizer, Placed, PlayerInput, RotateEvent, Settings, TSpinEvent,
+    Timing, BOTTOM_GRID, LEFT_GRID, RIGHT_GRID, SQUARE_SIZE,
 };
 
-#[derive(Resource)]
-pub struct DropTimer(pub Timer);
-
 #[derive(Resource)]
 pub struct NextPieces(pub Vec<Entity>);
 
-pub struct CanHoldPiece(bool);
-
-impl Default for CanHoldPiece {
-    fn default() -> Self {
-        CanHoldPiece(true)
-    }
-}
+/// The rules engine. Systems step it with player input and mirror its state into sprites.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct Engine(pub Game);
 
+/// Sprites of the placed cells. Row 0 is the top row of the grid.
 #[derive(Resource, Debug)]
 pub struct PlacedPieces(pub Vec<Vec<Option<Entity>>>);
 
@@ -39,38 +36,40 @@ pub struct HoldPieceEvent;
 //      Remove this component if not colliding with bottom_grid or another piece
 // if timer finishes and has marker component, place piece`
 
+#[allow(clippy::too_many_arguments)]
 pub fn place_piece(
-    query: Query<&Children, With<Active>>,
-    mut child_query: Query<(&GlobalTransform, &mut Transform), Without<Children>>,
+    children_query: Query<&Children>,
+    mut child_query: Query<&mut Transform, Without<Children>>,
     mut commands: Commands,
-    mut next_piece_query: Query<(&Children, &mut Transform, &PieceType), With<Children>>,
+    engine: Res<Engine>,
     mut ev_piece_placed: EventReader<PiecePlacedEvent>,
     mut ev_clear: EventWriter<ClearEvent>,
-    mut next_pieces: ResMut<NextPieces>,
     mut placed_pieces: ResMut<PlacedPieces>,
     mut ev_game_over: EventWriter<GameOverEvent>,
+    mut ev_t_spin: EventWriter<TSpinEvent>,
 ) {
     let ev = if let Some(event) = ev_piece_placed.read().next() {
         event
     } else {
         return;
     };
+    let piece = if let Some(piece) = engine.last_locked {
+        piece
+    } else {
+        return;
+    };
 
     // place piece
-    let children = query.get(ev.0).unwrap();
-    for &child in children.iter() {
-        let (child_global_transform, mut child_transform) = child_query.get_mut(child).unwrap();
-        let row = get_row_no_clamp(child_global_transform.translation().y);
-        let col = get_col(child_global_transform.translation().x);
-
-        *child_transform = child_global_transform.compute_transform();
-        println!("row: {}, col: {}", row, col);
-        if row >= 20 {
-            println!("game over");
-            ev_game_over.send(GameOverEvent);
-            return;
+    let children = children_query.get(ev.0).unwrap();
+    for (&child, (x, y)) in children.iter().zip(piece.cells()) {
+        if y >= VISIBLE_HEIGHT as i32 {
+            commands.entity(child).despawn();
+            continue;
         }
-        placed_pieces.0[row][col] = Some(child);
+
+        let mut child_transform = child_query.get_mut(child).unwrap();
+        child_transform.translation = cell_translation(x, y);
+        placed_pieces.0[placed_row(y)][x as usize] = Some(child);
 
         commands.entity(child).remove::<Active>();
         commands.entity(child).insert(Placed);
@@ -78,181 +77,339 @@ pub fn place_piece(
     }
     commands.entity(ev.0).despawn();
 
+    if let Some(spin) = engine.last_spin {
+        ev_t_spin.send(TSpinEvent {
+            spin,
+            lines: engine.clearing_rows().len(),
+        });
+    }
+
+    if engine.is_game_over() {
+        println!("game over");
+        ev_game_over.send(GameOverEvent);
+        return;
+    }
+
     // check for full rows
-    let rows_to_remove: Vec<usize> = placed_pieces
-        .0
-        .iter()
-        .enumerate()
-        .filter(|(_, row)| row.iter().all(|&cell| cell.is_some()))
-        .filter(|(_, row)| row.len() == 10)
-        .map(|(i, _)| i)
-        .collect();
-    //println!("{:?}", rows_to_remove);
-    //println!("{:?}", placed_pieces);
-
-    if rows_to_remove.len() > 0 {
-        println!("placed_pieces: {:?}", placed_pieces.0);
-        ev_clear.send(ClearEvent(rows_to_remove));
+    if let Some(clear) = &engine.last_clear {
+        ev_clear.send(ClearEvent {
+            rows: clear.rows.iter().map(|&y| placed_row(y as i32)).collect(),
+            spin: clear.spin,
+            back_to_back: clear.back_to_back,
+            combo: clear.combo,
+            perfect: clear.perfect,
+            garbage: clear.garbage,
+        });
+    }
+}
+
+/// Brings in the next piece once the last one has locked, its rows have cleared and the entry
+/// delay has passed. Hold and rotate keys held down by then swap the piece into hold and turn it
+/// as it comes in, when the ruleset allows.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_next_piece(
+    timing: Res<Timing>,
+    gravity: Res<Gravity>,
+    settings: Res<Settings>,
+    input: Res<PlayerInput>,
+    mut sprites: PieceSprites,
+    mut commands: Commands,
+    mut engine: ResMut<Engine>,
+    mut next_pieces: ResMut<NextPieces>,
+    mut randomizer: ResMut<PieceRandomizer>,
+    mut waited: Local<u32>,
+    mut ev_hold: EventWriter<HoldPieceEvent>,
+    mut ev_game_over: EventWriter<GameOverEvent>,
+) {
+    if engine.active.is_some() || engine.is_game_over() {
+        *waited = 0;
+        return;
+    }
+    if !engine.clearing_rows().is_empty() {
+        return;
+    }
+    if *waited < timing.are {
+        *waited += 1;
         return;
     }
 
-    // get next piece
-    let next_piece = next_pieces.0.remove(0);
-    let (children, mut transform, piece_type) = next_piece_query.get_mut(next_piece).unwrap();
-    for child in children {
-        commands.entity(*child).insert(Active);
+    let mut events = engine.step(Input::Spawn);
+    if events.is_empty() {
+        return;
     }
-    commands.entity(next_piece).insert(Active);
-    // if square or straight, position needs to move shifted up and to the right by SQUARE_SIZE
-    // / 2.0
-    move_piece_to_board(&piece_type, &mut transform.translation, &placed_pieces);
+    *waited = 0;
 
-    let new_piece = get_random_piece();
-    let entities = build_piece(
+    let active = activate_next_piece(
         &mut commands,
-        &new_piece,
-        Vec3::new(RIGHT_GRID * 1.5, 90.0, -1.0),
+        &sprites.children,
+        &mut engine,
+        &mut next_pieces,
+        &mut randomizer,
     );
-    next_pieces.0.push(entities[0]);
-}
 
-pub fn move_piece_to_board(
-    piece_type: &PieceType,
-    translation: &mut Vec3,
-    placed_pieces: &PlacedPieces,
-) {
-    match piece_type {
-        PieceType::Square => {
-            *translation = Vec3::new(-SQUARE_SIZE / 2.0, 270.0 - SQUARE_SIZE / 2., -1.0);
+    if settings.ruleset.ihs && input.pressed(Action::Hold) && !events.contains(&GameEvent::GameOver)
+    {
+        if let Some(hold_events) = hold_active(
+            &mut commands,
+            &mut sprites,
+            active,
+            &mut engine,
+            &mut next_pieces,
+            &mut randomizer,
+        ) {
+            events = hold_events;
+            ev_hold.send(HoldPieceEvent);
         }
-        PieceType::Straight => {
-            *translation = Vec3::new(SQUARE_SIZE / 2.0, 240.0 + SQUARE_SIZE / 2., -1.0);
+    }
+
+    if events.contains(&GameEvent::GameOver) {
+        println!("game over");
+        ev_game_over.send(GameOverEvent);
+        return;
+    }
+    if settings.ruleset.irs {
+        let rotation = [
+            (Action::RotateClockwise, Input::RotateClockwise),
+            (
+                Action::RotateCounterClockwise,
+                Input::RotateCounterClockwise,
+            ),
+            (Action::Rotate180, Input::Rotate180),
+        ]
+        .into_iter()
+        .find(|&(action, _)| input.pressed(action));
+        if let Some((_, rotation)) = rotation {
+            engine.step(rotation);
         }
+    }
+    fall_instantly(&mut engine, &gravity);
+}
+
+/// Marks the first of the next pieces active, now that the engine has brought it in, and deals a
+/// new piece to the back of the queue. Returns the entity of the piece made active.
+fn activate_next_piece(
+    commands: &mut Commands,
+    children_query: &Query<&Children>,
+    engine: &mut Game,
+    next_pieces: &mut NextPieces,
+    randomizer: &mut PieceRandomizer,
+) -> Entity {
+    if let Some(new_piece) = randomizer.next() {
+        engine.push_next(new_piece);
+        let entities = build_piece(
+            commands,
+            &new_piece.into(),
+            engine.rotation_system,
+            Vec3::new(0.0, NEXT_PIECE_Y, -1.0),
+        );
+        next_pieces.0.push(entities[0]);
+    }
+
+    let next_piece = next_pieces.0.remove(0);
+    activate(commands, children_query, next_piece);
+    next_piece
+}
+
+/// Moves a piece out of the next queue or hold onto the board.
+fn activate(commands: &mut Commands, children_query: &Query<&Children>, piece: Entity) {
+    for &child in children_query.get(piece).unwrap() {
+        commands.entity(child).remove::<Hold>();
+        commands.entity(child).insert(Active);
+    }
+    commands.entity(piece).remove::<Hold>();
+    commands.entity(piece).insert(Active);
+}
+
+/// The piece sprites moved by a hold: their parts, where they are, and the piece in hold if any.
+#[derive(SystemParam)]
+pub struct PieceSprites<'w, 's> {
+    children: Query<'w, 's, &'static Children>,
+    transforms: Query<'w, 's, &'static mut Transform>,
+    held: Query<'w, 's, Entity, (With<Hold>, With<Children>)>,
+}
+
+/// Puts the `active` piece into hold, in the engine and on screen, and brings out the held piece
+/// or the next one in its place. Returns the events of the hold, or `None` when the engine does
+/// not allow holding.
+fn hold_active(
+    commands: &mut Commands,
+    sprites: &mut PieceSprites,
+    active: Entity,
+    engine: &mut Game,
+    next_pieces: &mut NextPieces,
+    randomizer: &mut PieceRandomizer,
+) -> Option<Vec<GameEvent>> {
+    let events = engine.step(Input::Hold);
+    let swapped = if let Some(GameEvent::Held { swapped }) = events.first() {
+        *swapped
+    } else {
+        return None;
+    };
+
+    let held = sprites.held.get_single().ok();
+    let PieceSprites {
+        children: children_query,
+        transforms,
+        ..
+    } = sprites;
+
+    // move to hold in the spawn orientation
+    let held_kind = engine.hold.unwrap();
+    let children = children_query.get(active).unwrap();
+    for (&child, translation) in children.iter().zip(piece_part_translations(
+        engine.rotation_system,
+        held_kind,
+        RotationState::Zero,
+    )) {
+        commands.entity(child).remove::<Active>();
+        commands.entity(child).insert(Hold);
+        transforms.get_mut(child).unwrap().translation = translation;
+    }
+    commands.entity(active).remove::<Active>();
+    commands.entity(active).insert(Hold);
+    move_to_hold(&mut transforms.get_mut(active).unwrap().translation);
+
+    match (swapped, held) {
+        (Some(_), Some(held)) => activate(commands, children_query, held),
         _ => {
-            *translation = Vec3::new(0.0, 240.0, -1.0);
+            activate_next_piece(commands, children_query, engine, next_pieces, randomizer);
         }
     }
+    Some(events)
+}
+
+/// At 20G the active piece never hangs in the air: drops it as far as it goes.
+pub fn fall_instantly(engine: &mut Game, gravity: &Gravity) {
+    if !gravity.is_instant() {
+        return;
+    }
+    while !engine.step(Input::Gravity).is_empty() {}
+}
+
+/// Moves the active piece sprites to where the engine has the active piece.
+pub fn sync_active_piece(
+    engine: Res<Engine>,
+    mut query: Query<(&Children, &mut Transform), With<Active>>,
+    mut child_query: Query<&mut Transform, Without<Children>>,
+) {
+    let piece = if let Some(piece) = engine.active {
+        piece
+    } else {
+        return;
+    };
+    let (children, mut transform) = if let Ok(active) = query.get_single_mut() {
+        active
+    } else {
+        return;
+    };
 
-    // check if we need to move the piece up to avoid collision with placed pieces
-    let avoid_collision_offset = placed_pieces
-        .0
-        .iter()
-        .take(2)
-        .filter(|row| row.iter().any(|cell| cell.is_some()))
-        .collect::<Vec<_>>()
-        .len() as f32
-        * SQUARE_SIZE;
-    translation.y += avoid_collision_offset;
-
-    println!("avoid_collision_offset : {:?}", avoid_collision_offset);
+    transform.translation = cell_translation(piece.x, piece.y);
+    for (&child, translation) in children.iter().zip(piece_part_translations(
+        piece.system,
+        piece.kind,
+        piece.rotation,
+    )) {
+        if let Ok(mut child_transform) = child_query.get_mut(child) {
+            child_transform.translation = translation;
+        }
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn hold_piece(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    placed_pieces: Res<PlacedPieces>,
+    input: Res<PlayerInput>,
+    gravity: Res<Gravity>,
+    mut sprites: PieceSprites,
     mut commands: Commands,
-    mut query: Query<(&Children, Entity, &mut Transform), (With<Active>, Without<Hold>)>,
-    mut held_query: Query<(&Children, &PieceType, Entity, &mut Transform), With<Hold>>,
-    mut next_piece_query: Query<
-        (&Children, &mut Transform, &PieceType),
-        (With<Children>, Without<Hold>, Without<Active>),
-    >,
+    mut engine: ResMut<Engine>,
+    query: Query<Entity, (With<Active>, With<Children>)>,
     mut next_pieces: ResMut<NextPieces>,
-    mut ev_piece_placed: EventReader<PiecePlacedEvent>,
+    mut randomizer: ResMut<PieceRandomizer>,
     mut ev_hold: EventWriter<HoldPieceEvent>,
-    mut ev_reset_game: EventReader<RestartGameEvent>,
-    // TODO: turn into component
-    mut can_hold: Local<CanHoldPiece>,
+    mut ev_game_over: EventWriter<GameOverEvent>,
 ) {
-    if ev_reset_game.read().count() > 0 {
-        can_hold.0 = true;
-        ev_reset_game.clear();
-    }
-    if ev_piece_placed.read().count() > 0 {
-        can_hold.0 = true;
-    }
-    if !keyboard_input.just_pressed(KeyCode::KeyC) {
-        return;
-    }
-    if !can_hold.0 {
+    if !input.just_pressed(Action::Hold) {
         return;
     }
 
-    can_hold.0 = false;
-
-    let (children, entity, mut transform) = if let Ok(piece) = query.get_single_mut() {
+    let active = if let Ok(piece) = query.get_single() {
         piece
     } else {
         return;
     };
 
-    for &child in children.iter() {
-        commands.entity(child).remove::<Active>();
-        commands.entity(child).insert(Hold);
-    }
-
-    commands.entity(entity).remove::<Active>();
-    commands.entity(entity).insert(Hold);
-
-    // move to hold
-    move_to_hold(&mut transform.translation);
-    if let Ok((held_children, piece_type, held_entity, mut transform)) = held_query.get_single_mut()
-    {
-        for &child in held_children.iter() {
-            commands.entity(child).remove::<Hold>();
-            commands.entity(child).insert(Active);
-        }
+    let events = if let Some(events) = hold_active(
+        &mut commands,
+        &mut sprites,
+        active,
+        &mut engine,
+        &mut next_pieces,
+        &mut randomizer,
+    ) {
+        events
+    } else {
+        return;
+    };
 
-        commands.entity(held_entity).remove::<Hold>();
-        commands.entity(held_entity).insert(Active);
-        // move to board
-        move_piece_to_board(&piece_type, &mut transform.translation, &placed_pieces);
+    if events.contains(&GameEvent::GameOver) {
+        ev_game_over.send(GameOverEvent);
     } else {
-        // pull from nextPieces
-        let next_piece = next_pieces.0.remove(0);
-        let (children, mut next_transform, piece_type) =
-            next_piece_query.get_mut(next_piece).unwrap();
-        for child in children {
-            commands.entity(*child).insert(Active);
-        }
-        commands.entity(next_piece).insert(Active);
-        // if square or straight, position needs to move shifted up and to the right by SQUARE_SIZE
-        // / 2.0
-        move_piece_to_board(&piece_type, &mut next_transform.translation, &placed_pieces);
-        let new_piece = get_random_piece();
-        let entities = build_piece(
-            &mut commands,
-            &new_piece,
-            Vec3::new(0.0, NEXT_PIECE_Y, -1.0),
-        );
-        next_pieces.0.push(entities[0]);
+        fall_instantly(&mut engine, &gravity);
     }
 
     ev_hold.send(HoldPieceEvent);
 }
 
-pub fn get_row_no_clamp(translation_y: f32) -> usize {
-    let row = ((TOP_GRID - SQUARE_SIZE) - translation_y) / SQUARE_SIZE;
-    if row < 0.0 {
-        return 20;
-    }
-    return row as usize;
+/// World translation of the bottom left corner of a grid cell, where `y` counts up from the bottom
+/// row.
+pub fn cell_translation(x: i32, y: i32) -> Vec3 {
+    Vec3::new(
+        LEFT_GRID + x as f32 * SQUARE_SIZE,
+        BOTTOM_GRID + y as f32 * SQUARE_SIZE,
+        -1.0,
+    )
 }
 
-pub fn get_row(translation_y: f32) -> usize {
-    let row = (((TOP_GRID - SQUARE_SIZE) - translation_y) / SQUARE_SIZE) as usize;
-    return row.clamp(0, 19);
+/// Visible board cell under a point of the world, the inverse of [`cell_translation`].
+pub fn translation_cell(translation: Vec2) -> Option<(i32, i32)> {
+    let x = ((translation.x - LEFT_GRID) / SQUARE_SIZE).floor() as i32;
+    let y = ((translation.y - BOTTOM_GRID) / SQUARE_SIZE).floor() as i32;
+    let on_board = (0..BOARD_WIDTH as i32).contains(&x) && (0..VISIBLE_HEIGHT as i32).contains(&y);
+    on_board.then_some((x, y))
 }
 
-pub fn get_col(translation_x: f32) -> usize {
-    let col = ((translation_x - LEFT_GRID) / SQUARE_SIZE) as usize;
-
-    return col.clamp(0, 9);
+/// Row of `PlacedPieces` for a board row counted from the bottom.
+pub fn placed_row(y: i32) -> usize {
+    VISIBLE_HEIGHT - 1 - y as usize
 }
 
 pub const HOLD_PIECE_Y: f32 = 160.0;
 pub const NEXT_PIECE_Y: f32 = 140.0;
 
+/// Builds the sprites of a piece already in hold, such as the one a puzzle starts with.
+pub fn build_held_piece(commands: &mut Commands, kind: PieceKind, system: RotationSystem) {
+    let mut translation = Vec3::ZERO;
+    move_to_hold(&mut translation);
+    for entity in build_piece(commands, &kind.into(), system, translation) {
+        commands.entity(entity).insert(Hold);
+    }
+}
+
+/// Builds a placed sprite for every visible cell of `board`.
+pub fn build_board(commands: &mut Commands, board: &Board, placed_pieces: &mut PlacedPieces) {
+    for y in 0..VISIBLE_HEIGHT as i32 {
+        for x in 0..BOARD_WIDTH as i32 {
+            if let Some(cell) = board.get(x, y) {
+                let translation = cell_translation(x, y);
+                let entity = commands
+                    .spawn((PiecePartBundle::new(&cell.into(), &translation), Placed))
+                    .id();
+                placed_pieces.0[placed_row(y)][x as usize] = Some(entity);
+            }
+        }
+    }
+}
+
 fn move_to_hold(translation: &mut Vec3) {
     *translation = Vec3::new(LEFT_GRID - SQUARE_SIZE * 4.0, HOLD_PIECE_Y - 20.0, -1.0);
 }
@@ -270,11 +427,9 @@ pub fn position_next_pieces(next_pieces: ResMut<NextPieces>, mut query: Query<&m
 }
 
 pub fn shift_active_down(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    time: Res<Time>,
-    mut query: Query<&mut Transform, (With<Active>, Without<Parent>)>,
-    mut timer: ResMut<DropTimer>,
-    mut ev_collision: EventReader<CollisionEvent>,
+    input: Res<PlayerInput>,
+    mut engine: ResMut<Engine>,
+    mut gravity: ResMut<Gravity>,
     mut ev_rotate: EventReader<RotateEvent>,
     mut ev_drop_piece: EventReader<DropPieceEvent>,
     mut ev_piece_placed: EventReader<PiecePlacedEvent>,
@@ -289,35 +444,19 @@ pub fn shift_active_down(
         return;
     }
 
-    let mut transform = if let Ok(piece) = query.get_single_mut() {
-        piece
-    } else {
+    if engine.active.is_none() {
         return;
-    };
-
-    if keyboard_input.pressed(KeyCode::ArrowDown) {
-        timer.0.reset();
     }
 
-    if !timer.0.tick(time.delta()).just_finished() {
+    // soft drop moves the piece down on its own timer
+    if input.pressed(Action::SoftDrop) {
+        gravity.reset();
         return;
     }
 
-    let mut collisions: HashSet<&Collision> = HashSet::new();
-
-    for collision in ev_collision.read() {
-        collisions.extend(&collision.collision);
-    }
-
-    if collisions.contains(&Collision::Top) {
-        return;
-    }
-
-    if transform.translation.y > BOTTOM_GRID - SQUARE_SIZE
-        && !keyboard_input.pressed(KeyCode::ArrowDown)
-    {
-        transform.translation.y -= SQUARE_SIZE;
+    for _ in 0..gravity.tick() {
+        if engine.step(Input::Gravity).is_empty() {
+            break;
+        }
     }
-
-    //println!("{:?}", transform.translation);
 }
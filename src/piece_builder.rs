@@ -1,8 +1,11 @@
-use bevy::{prelude::*, sprite::Anchor};
+use std::collections::VecDeque;
 
-use rand::Rng;
+use bevy::{prelude::*, sprite::Anchor};
 
-use crate::{DespawnOnRestart, RotationState};
+use crate::{
+    engine::{Cell, PieceKind, Randomizer, RandomizerKind, RotationState, RotationSystem},
+    DespawnOnRestart,
+};
 
 pub const SQUARE_SIZE: f32 = 30.0;
 
@@ -17,7 +20,6 @@ pub struct PieceBundle {
     spatial_bundle: SpatialBundle,
     type_bundle: PieceType,
     despawn: DespawnOnRestart,
-    rotation_state: RotationState,
 }
 
 #[derive(Component, Clone, Eq, PartialEq, Debug)]
@@ -30,9 +32,49 @@ pub enum PieceType {
     ReverseZ,
     Square,
     Ghost(Box<PieceType>),
+    /// A single cell of a garbage row.
+    Garbage,
+}
+
+impl From<PieceKind> for PieceType {
+    fn from(piece_kind: PieceKind) -> Self {
+        match piece_kind {
+            PieceKind::Straight => PieceType::Straight,
+            PieceKind::L => PieceType::L,
+            PieceKind::ReverseL => PieceType::ReverseL,
+            PieceKind::T => PieceType::T,
+            PieceKind::Z => PieceType::Z,
+            PieceKind::ReverseZ => PieceType::ReverseZ,
+            PieceKind::Square => PieceType::Square,
+        }
+    }
+}
+
+impl From<Cell> for PieceType {
+    fn from(cell: Cell) -> Self {
+        match cell {
+            Cell::Piece(piece_kind) => piece_kind.into(),
+            Cell::Garbage => PieceType::Garbage,
+        }
+    }
 }
 
 impl PieceType {
+    /// The piece this is built as, `None` for garbage.
+    pub fn kind(&self) -> Option<PieceKind> {
+        match self {
+            PieceType::Straight => Some(PieceKind::Straight),
+            PieceType::L => Some(PieceKind::L),
+            PieceType::ReverseL => Some(PieceKind::ReverseL),
+            PieceType::T => Some(PieceKind::T),
+            PieceType::Z => Some(PieceKind::Z),
+            PieceType::ReverseZ => Some(PieceKind::ReverseZ),
+            PieceType::Square => Some(PieceKind::Square),
+            PieceType::Ghost(piece_type) => piece_type.kind(),
+            PieceType::Garbage => None,
+        }
+    }
+
     fn sprite(&self) -> Sprite {
         let color = match self {
             PieceType::Straight => Color::rgb_u8(12, 175, 200),
@@ -43,6 +85,7 @@ impl PieceType {
             PieceType::ReverseZ => Color::rgb_u8(195, 17, 40),
             PieceType::Square => Color::rgb_u8(205, 180, 2),
             PieceType::Ghost(_) => Color::rgba(0.0, 0.0, 0.0, 0.5),
+            PieceType::Garbage => Color::rgb_u8(110, 110, 110),
         };
 
         Sprite {
@@ -64,7 +107,6 @@ impl PieceBundle {
             },
             type_bundle: piece_type.clone(),
             despawn: DespawnOnRestart,
-            rotation_state: default(),
         }
     }
 }
@@ -88,361 +130,97 @@ impl PiecePartBundle {
     }
 }
 
-pub fn get_random_piece() -> PieceType {
-    match rand::thread_rng().gen_range(0..7) {
-        0 => PieceType::Straight,
-        1 => PieceType::L,
-        2 => PieceType::ReverseL,
-        3 => PieceType::T,
-        4 => PieceType::Z,
-        5 => PieceType::ReverseZ,
-        6 => PieceType::Square,
-        _ => panic!("Invalid random number"),
+/// Deals the pieces for the current game. Every new piece should be pulled from here so a game
+/// can be reproduced from its seed.
+#[derive(Resource)]
+pub struct PieceRandomizer {
+    pub seed: u64,
+    /// Deals the pieces, or `None` once a fixed queue runs out.
+    randomizer: Option<Box<dyn Randomizer>>,
+    /// Pieces dealt before any from the randomizer.
+    queue: VecDeque<PieceKind>,
+}
+
+impl PieceRandomizer {
+    pub fn new(kind: RandomizerKind, seed: u64) -> PieceRandomizer {
+        PieceRandomizer {
+            seed,
+            randomizer: Some(kind.build(seed)),
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Deals exactly `queue` and nothing after it.
+    pub fn fixed(queue: &[PieceKind]) -> PieceRandomizer {
+        PieceRandomizer {
+            seed: 0,
+            randomizer: None,
+            queue: queue.iter().copied().collect(),
+        }
+    }
+
+    /// Deals `queue` before carrying on with the randomizer.
+    pub fn deal_first(&mut self, queue: &[PieceKind]) {
+        self.queue = queue.iter().copied().collect();
+    }
+
+    pub fn next(&mut self) -> Option<PieceKind> {
+        self.queue
+            .pop_front()
+            .or_else(|| self.randomizer.as_mut().map(|randomizer| randomizer.next()))
     }
 }
 
-pub fn build_active_piece(commands: &mut Commands, piece_type: &PieceType, pos: Vec3) {
-    internal_build_piece(commands, piece_type, pos, Some(Active), false);
+pub fn build_active_piece(
+    commands: &mut Commands,
+    piece_type: &PieceType,
+    system: RotationSystem,
+    pos: Vec3,
+) -> Entity {
+    internal_build_piece(commands, piece_type, system, pos, Some(Active))[0]
 }
 
-pub fn build_piece(commands: &mut Commands, piece_type: &PieceType, pos: Vec3) -> [Entity; 5] {
-    return internal_build_piece(commands, piece_type, pos, None, false);
+pub fn build_piece(
+    commands: &mut Commands,
+    piece_type: &PieceType,
+    system: RotationSystem,
+    pos: Vec3,
+) -> [Entity; 5] {
+    internal_build_piece(commands, piece_type, system, pos, None)
 }
 
 fn internal_build_piece(
     commands: &mut Commands,
     piece_type: &PieceType,
+    system: RotationSystem,
     pos: Vec3,
     active: Option<Active>,
-    is_ghost: bool,
 ) -> [Entity; 5] {
-    match piece_type {
-        PieceType::Straight => {
-            let piece_type = if is_ghost {
-                &PieceType::Ghost(Box::new(piece_type.clone()))
-            } else {
-                piece_type
-            };
-
-            // Needed to change origin pivot point when rotating
-            // https://tetris.fandom.com/wiki/SRS?file=SRS-pieces.png
-            let straight_pos = Vec3::new(pos.x + SQUARE_SIZE / 2., pos.y + SQUARE_SIZE / 2., pos.z);
-
-            let piece = PieceBundle::new(&piece_type, &straight_pos);
-            let piece = commands.spawn(piece).id();
-
-            let relative_pos = Vec3::new(SQUARE_SIZE / 2., SQUARE_SIZE / 2., 0.0);
-
-            let child1 = commands
-                .spawn(PiecePartBundle::new(&piece_type, &relative_pos))
-                .id();
-            let child2 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x + SQUARE_SIZE, relative_pos.y, relative_pos.z),
-                ))
-                .id();
-            let child3 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x - SQUARE_SIZE, relative_pos.y, relative_pos.z),
-                ))
-                .id();
-            let child4 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(
-                        relative_pos.x - SQUARE_SIZE * 2.0,
-                        relative_pos.y,
-                        relative_pos.z,
-                    ),
-                ))
-                .id();
-
-            combine_piece_parts(
-                commands,
-                piece,
-                active,
-                vec![child1, child2, child3, child4],
-            );
+    let piece = PieceBundle::new(piece_type, &pos);
+    let piece = commands.spawn(piece).id();
 
-            return [piece, child1, child2, child3, child4];
-        }
-        PieceType::ReverseL => {
-            let piece_type = if is_ghost {
-                &PieceType::Ghost(Box::new(piece_type.clone()))
-            } else {
-                piece_type
-            };
-
-            let piece = PieceBundle::new(&piece_type, &pos);
-            let piece = commands.spawn(piece).id();
-
-            let relative_pos = Vec3::new(0., 0., 0.0);
-            let child1 = commands
-                .spawn(PiecePartBundle::new(&piece_type, &relative_pos))
-                .id();
-            let child2 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x + SQUARE_SIZE, relative_pos.y, relative_pos.z),
-                ))
-                .id();
-            let child3 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x - SQUARE_SIZE, relative_pos.y, relative_pos.z),
-                ))
-                .id();
-            let child4 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(
-                        relative_pos.x - SQUARE_SIZE,
-                        relative_pos.y + SQUARE_SIZE,
-                        relative_pos.z,
-                    ),
-                ))
-                .id();
-            combine_piece_parts(
-                commands,
-                piece,
-                active,
-                vec![child1, child2, child3, child4],
-            );
-            return [piece, child1, child2, child3, child4];
-        }
-        PieceType::L => {
-            let piece_type = if is_ghost {
-                &PieceType::Ghost(Box::new(piece_type.clone()))
-            } else {
-                piece_type
-            };
-
-            let piece = PieceBundle::new(&piece_type, &pos);
-            let piece = commands.spawn(piece).id();
-
-            let relative_pos = Vec3::new(0., 0., 0.0);
-            let child1 = commands
-                .spawn(PiecePartBundle::new(&piece_type, &relative_pos))
-                .id();
-            let child2 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x + SQUARE_SIZE, relative_pos.y, relative_pos.z),
-                ))
-                .id();
-            let child3 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x - SQUARE_SIZE, relative_pos.y, relative_pos.z),
-                ))
-                .id();
-            let child4 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(
-                        relative_pos.x + SQUARE_SIZE,
-                        relative_pos.y + SQUARE_SIZE,
-                        relative_pos.z,
-                    ),
-                ))
-                .id();
-            combine_piece_parts(
-                commands,
-                piece,
-                active,
-                vec![child1, child2, child3, child4],
-            );
-            return [piece, child1, child2, child3, child4];
-        }
-        PieceType::Z => {
-            let piece_type = if is_ghost {
-                &PieceType::Ghost(Box::new(piece_type.clone()))
-            } else {
-                piece_type
-            };
+    // children are laid out in the spawn orientation, the systems mirroring the engine move them
+    // around after that
+    let kind = piece_type
+        .kind()
+        .expect("garbage is never built as a whole piece");
+    let children = piece_part_translations(system, kind, RotationState::Zero)
+        .map(|pos| commands.spawn(PiecePartBundle::new(piece_type, &pos)).id());
 
-            let piece = PieceBundle::new(&piece_type, &pos);
-            let piece = commands.spawn(piece).id();
+    combine_piece_parts(commands, piece, active, children.to_vec());
 
-            let relative_pos = Vec3::new(0., 0., 0.0);
-            let child1 = commands
-                .spawn(PiecePartBundle::new(&piece_type, &relative_pos))
-                .id();
-            let child2 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x, relative_pos.y + SQUARE_SIZE, relative_pos.z),
-                ))
-                .id();
-            let child3 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(
-                        relative_pos.x + SQUARE_SIZE,
-                        relative_pos.y + SQUARE_SIZE,
-                        relative_pos.z,
-                    ),
-                ))
-                .id();
-            let child4 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x - SQUARE_SIZE, relative_pos.y, relative_pos.z),
-                ))
-                .id();
-            combine_piece_parts(
-                commands,
-                piece,
-                active,
-                vec![child1, child2, child3, child4],
-            );
-            return [piece, child1, child2, child3, child4];
-        }
-        PieceType::ReverseZ => {
-            let piece_type = if is_ghost {
-                &PieceType::Ghost(Box::new(piece_type.clone()))
-            } else {
-                piece_type
-            };
-
-            let piece = PieceBundle::new(&piece_type, &pos);
-            let piece = commands.spawn(piece).id();
-
-            let relative_pos = Vec3::new(0., 0., 0.0);
-            let child1 = commands
-                .spawn(PiecePartBundle::new(&piece_type, &relative_pos))
-                .id();
-            let child2 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x, relative_pos.y + SQUARE_SIZE, relative_pos.z),
-                ))
-                .id();
-            let child3 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(
-                        relative_pos.x - SQUARE_SIZE,
-                        relative_pos.y + SQUARE_SIZE,
-                        relative_pos.z,
-                    ),
-                ))
-                .id();
-            let child4 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x + SQUARE_SIZE, relative_pos.y, relative_pos.z),
-                ))
-                .id();
-            combine_piece_parts(
-                commands,
-                piece,
-                active,
-                vec![child1, child2, child3, child4],
-            );
-            return [piece, child1, child2, child3, child4];
-        }
-        PieceType::T => {
-            let piece_type = if is_ghost {
-                &PieceType::Ghost(Box::new(piece_type.clone()))
-            } else {
-                piece_type
-            };
-
-            let piece = PieceBundle::new(&piece_type, &pos);
-            let piece = commands.spawn(piece).id();
-
-            let relative_pos = Vec3::new(0., 0., 0.0);
-            let child1 = commands
-                .spawn(PiecePartBundle::new(&piece_type, &relative_pos))
-                .id();
-            let child2 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x, relative_pos.y + SQUARE_SIZE, relative_pos.z),
-                ))
-                .id();
-            let child3 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x + SQUARE_SIZE, relative_pos.y, relative_pos.z),
-                ))
-                .id();
-            let child4 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x - SQUARE_SIZE, relative_pos.y, relative_pos.z),
-                ))
-                .id();
-            combine_piece_parts(
-                commands,
-                piece,
-                active,
-                vec![child1, child2, child3, child4],
-            );
-            return [piece, child1, child2, child3, child4];
-        }
-        PieceType::Square => {
-            let piece_type = if is_ghost {
-                &PieceType::Ghost(Box::new(piece_type.clone()))
-            } else {
-                piece_type
-            };
-
-            // Needed to change origin pivot point when rotating
-            // https://tetris.fandom.com/wiki/SRS?file=SRS-pieces.png
-            // Sidenote: the reason why we have to subtract the offset isntead of adding is because
-            // we are "moving" the square left and down relative to (0,0) so that the pivot point is in the center
-
-            // Deeper Explanation:
-            // The center of the bottom left block is the original pivot point ((0,0) as its relative position) of as the rest of the blocks are
-            // built relative to the bottom left block. The pivot point should be in the center of the square,
-            // so we need to offset the originl pivot point by the center of the botom-left block
-            // so that the relative position of the bottom left block is (-15,-15), and the center of the square is at (0,0).
-            let square_pos = Vec3::new(pos.x - SQUARE_SIZE / 2., pos.y - SQUARE_SIZE / 2., pos.z);
-            let piece = PieceBundle::new(&piece_type, &square_pos);
-            let piece = commands.spawn(piece).id();
+    [piece, children[0], children[1], children[2], children[3]]
+}
 
-            let relative_pos = Vec3::new(-SQUARE_SIZE / 2., -SQUARE_SIZE / 2., 0.0);
-            let child1 = commands
-                .spawn(PiecePartBundle::new(&piece_type, &relative_pos))
-                .id();
-            let child2 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x + SQUARE_SIZE, relative_pos.y, relative_pos.z),
-                ))
-                .id();
-            let child3 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(relative_pos.x, relative_pos.y + SQUARE_SIZE, relative_pos.z),
-                ))
-                .id();
-            let child4 = commands
-                .spawn(PiecePartBundle::new(
-                    &piece_type,
-                    &Vec3::new(
-                        relative_pos.x + SQUARE_SIZE,
-                        relative_pos.y + SQUARE_SIZE,
-                        relative_pos.z,
-                    ),
-                ))
-                .id();
-            combine_piece_parts(
-                commands,
-                piece,
-                active,
-                vec![child1, child2, child3, child4],
-            );
-            return [piece, child1, child2, child3, child4];
-        }
-        PieceType::Ghost(piece_type) => {
-            return internal_build_piece(commands, piece_type, pos, None, true);
-        }
-    }
+/// Translations of each part of a piece relative to the piece origin.
+pub fn piece_part_translations(
+    system: RotationSystem,
+    piece_kind: PieceKind,
+    rotation: RotationState,
+) -> [Vec3; 4] {
+    system
+        .cells(piece_kind, rotation)
+        .map(|(x, y)| Vec3::new(x as f32 * SQUARE_SIZE, y as f32 * SQUARE_SIZE, 0.0))
 }
 
 fn combine_piece_parts(
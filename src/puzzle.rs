@@ -0,0 +1,178 @@
+use std::{error::Error, fs, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{Board, Cell, Game, PieceKind, BOARD_WIDTH, VISIBLE_HEIGHT};
+
+const PUZZLES_PATH: &str = "assets/puzzles";
+
+/// What solves a puzzle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PuzzleGoal {
+    /// Clear this many lines in total.
+    Lines(u32),
+    /// Clear this many lines with a single full T-spin.
+    TSpin(usize),
+    /// Clear every cell off the board.
+    PerfectClear,
+}
+
+impl PuzzleGoal {
+    pub fn describe(&self) -> String {
+        match self {
+            PuzzleGoal::Lines(1) => "Clear 1 line".to_string(),
+            PuzzleGoal::Lines(lines) => format!("Clear {} lines", lines),
+            PuzzleGoal::TSpin(1) => "T-Spin Single".to_string(),
+            PuzzleGoal::TSpin(2) => "T-Spin Double".to_string(),
+            PuzzleGoal::TSpin(3) => "T-Spin Triple".to_string(),
+            PuzzleGoal::TSpin(_) => "T-Spin".to_string(),
+            PuzzleGoal::PerfectClear => "Perfect Clear".to_string(),
+        }
+    }
+}
+
+/// A puzzle as written in `assets/puzzles/*.ron`.
+#[derive(Deserialize)]
+struct PuzzleFile {
+    name: String,
+    /// Rows of the board from the top down, sitting on the floor. `.` is empty, `G` garbage and a
+    /// guideline piece letter a cell of that piece.
+    board: Vec<String>,
+    /// Guideline piece letters in the order they are dealt, the first spawning right away.
+    queue: String,
+    #[serde(default)]
+    hold: Option<char>,
+    goal: PuzzleGoal,
+}
+
+/// A starting board, a fixed queue and hold, and a goal to reach with them.
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    pub name: String,
+    pub board: Board,
+    pub queue: Vec<PieceKind>,
+    pub hold: Option<PieceKind>,
+    pub goal: PuzzleGoal,
+}
+
+impl TryFrom<PuzzleFile> for Puzzle {
+    type Error = String;
+
+    fn try_from(file: PuzzleFile) -> Result<Puzzle, String> {
+        let piece = |letter: char| {
+            PieceKind::from_letter(letter).ok_or_else(|| format!("{:?} is not a piece", letter))
+        };
+
+        if file.board.len() > VISIBLE_HEIGHT {
+            return Err(format!("the board has more than {} rows", VISIBLE_HEIGHT));
+        }
+        let mut board = Board::default();
+        for (i, row) in file.board.iter().enumerate() {
+            if row.chars().count() != BOARD_WIDTH {
+                return Err(format!("row {:?} is not {} cells wide", row, BOARD_WIDTH));
+            }
+            let y = (file.board.len() - 1 - i) as i32;
+            for (x, letter) in row.chars().enumerate() {
+                let cell = match letter {
+                    '.' => None,
+                    'G' | 'g' => Some(Cell::Garbage),
+                    letter => Some(piece(letter)?.into()),
+                };
+                board.set(x as i32, y, cell);
+            }
+        }
+
+        let queue = file
+            .queue
+            .chars()
+            .map(piece)
+            .collect::<Result<Vec<_>, _>>()?;
+        if queue.is_empty() {
+            return Err("the queue is empty".to_string());
+        }
+
+        Ok(Puzzle {
+            name: file.name,
+            board,
+            queue,
+            hold: file.hold.map(piece).transpose()?,
+            goal: file.goal,
+        })
+    }
+}
+
+impl Puzzle {
+    /// A game on the puzzle board with its hold piece. The queue is dealt like any other.
+    pub fn game(&self) -> Game {
+        let mut game = Game::default();
+        game.board = self.board.clone();
+        game.hold = self.hold;
+        game
+    }
+}
+
+/// Every puzzle in `assets/puzzles`, ordered by file name.
+#[derive(Resource, Default)]
+pub struct Puzzles(pub Vec<Puzzle>);
+
+impl Puzzles {
+    /// Loads every puzzle it can, printing why the others could not be loaded.
+    pub fn load() -> Puzzles {
+        let dir = Path::new(PUZZLES_PATH);
+        let mut paths: Vec<_> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+                .collect(),
+            Err(err) => {
+                println!("could not load puzzles: {}", err);
+                return Puzzles::default();
+            }
+        };
+        paths.sort();
+
+        let puzzles = paths
+            .iter()
+            .filter_map(|path| {
+                fs::read_to_string(path)
+                    .map_err(Box::<dyn Error>::from)
+                    .and_then(|puzzle| Ok(ron::from_str::<PuzzleFile>(&puzzle)?))
+                    .and_then(|puzzle| Ok(Puzzle::try_from(puzzle)?))
+                    .map_err(|err| println!("could not load puzzle {}: {}", path.display(), err))
+                    .ok()
+            })
+            .collect();
+        Puzzles(puzzles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_puzzles_load() {
+        let count = fs::read_dir(PUZZLES_PATH).unwrap().count();
+        assert!(count > 0);
+        assert_eq!(Puzzles::load().0.len(), count);
+    }
+
+    #[test]
+    fn board_rows_sit_on_the_floor() {
+        let puzzle = Puzzle::try_from(PuzzleFile {
+            name: "test".to_string(),
+            board: vec!["T.........".to_string(), "GGGGGGGGG.".to_string()],
+            queue: "ti".to_string(),
+            hold: Some('O'),
+            goal: PuzzleGoal::Lines(1),
+        })
+        .unwrap();
+
+        assert_eq!(puzzle.board.get(0, 1), Some(PieceKind::T.into()));
+        assert_eq!(puzzle.board.get(8, 0), Some(Cell::Garbage));
+        assert_eq!(puzzle.board.get(9, 0), None);
+        assert_eq!(puzzle.queue, vec![PieceKind::T, PieceKind::Straight]);
+        assert_eq!(puzzle.hold, Some(PieceKind::Square));
+    }
+}
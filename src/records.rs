@@ -0,0 +1,69 @@
+use std::{collections::BTreeMap, error::Error, fs, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const RECORDS_PATH: &str = "records.ron";
+
+/// Personal bests, kept in `records.ron` between sessions.
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Records {
+    /// Best sprint for each line target.
+    pub sprint: BTreeMap<u32, SprintRecord>,
+    /// High scores for each ultra duration in seconds, best first.
+    pub ultra: BTreeMap<u32, Vec<u32>>,
+}
+
+/// Scores kept in each high score table.
+const HIGH_SCORES: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SprintRecord {
+    /// Ticks taken to reach the line target.
+    pub ticks: u32,
+    /// Ticks at which every 10th line was cleared.
+    pub splits: Vec<u32>,
+}
+
+impl Records {
+    /// Loads the records, starting from none if there is no file yet.
+    pub fn load() -> Records {
+        let path = Path::new(RECORDS_PATH);
+        if !path.exists() {
+            return Records::default();
+        }
+        fs::read_to_string(path)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|records| Ok(ron::from_str(&records)?))
+            .unwrap_or_else(|err| {
+                println!("could not load records: {}", err);
+                Records::default()
+            })
+    }
+
+    /// Enters `score` into the high score table of `seconds` long ultras. Returns its place, counted
+    /// from 0, or `None` if it did not make the table.
+    pub fn add_ultra_score(&mut self, seconds: u32, score: u32) -> Option<usize> {
+        let scores = self.ultra.entry(seconds).or_default();
+        let place = scores
+            .iter()
+            .position(|&high| score > high)
+            .unwrap_or(scores.len());
+        if place >= HIGH_SCORES {
+            return None;
+        }
+        scores.insert(place, score);
+        scores.truncate(HIGH_SCORES);
+        Some(place)
+    }
+
+    pub fn save(&self) {
+        let result = ron::to_string(self)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|records| Ok(fs::write(RECORDS_PATH, records)?));
+        if let Err(err) = result {
+            println!("could not save records: {}", err);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::{error::Error, fs, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameOverEvent, GoalReachedEvent, PieceRandomizer, PlayerInput, Ruleset, Settings};
+
+const REPLAY_DIR: &str = "replays";
+
+/// A whole game: the seed and rules it was played with and the input of every simulation tick.
+/// Playing the inputs back against the same seed and rules reproduces the game exactly.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub ruleset: Ruleset,
+    /// Fumen diagram of the board the game started from, if it was imported.
+    #[serde(default)]
+    pub fumen: Option<String>,
+    /// Run-length encoded: each input is held for the given number of ticks.
+    pub inputs: Vec<(u32, PlayerInput)>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Replay, Box<dyn Error>> {
+        Ok(ron::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, ron::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Appends one tick, extending the last run when the input did not change.
+    pub fn push(&mut self, input: PlayerInput) {
+        match self.inputs.last_mut() {
+            Some((ticks, last)) if *last == input => *ticks += 1,
+            _ => self.inputs.push((1, input)),
+        }
+    }
+}
+
+/// Inputs of the game being played, written to `replays/` when it ends.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct ReplayRecorder(pub Replay);
+
+/// The replay being played back in [`crate::GameState::Replay`].
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    run: usize,
+    tick: u32,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> ReplayPlayback {
+        ReplayPlayback {
+            replay,
+            run: 0,
+            tick: 0,
+        }
+    }
+
+    /// Input for the next tick, or `None` once the replay is over.
+    pub fn next(&mut self) -> Option<PlayerInput> {
+        let &(ticks, input) = self.replay.inputs.get(self.run)?;
+        self.tick += 1;
+        if self.tick >= ticks {
+            self.run += 1;
+            self.tick = 0;
+        }
+        Some(input)
+    }
+
+    pub fn rewind(&mut self) {
+        self.run = 0;
+        self.tick = 0;
+    }
+}
+
+pub fn save_replay(
+    randomizer: Res<PieceRandomizer>,
+    settings: Res<Settings>,
+    mut recorder: ResMut<ReplayRecorder>,
+    mut ev_game_over: EventReader<GameOverEvent>,
+    mut ev_goal: EventReader<GoalReachedEvent>,
+) {
+    let ended = ev_game_over.read().count() + ev_goal.read().count() > 0;
+    if !ended {
+        return;
+    }
+
+    recorder.seed = randomizer.seed;
+    recorder.ruleset = settings.ruleset;
+    let path = Path::new(REPLAY_DIR).join(format!("{}.ron", randomizer.seed));
+    let result = fs::create_dir_all(REPLAY_DIR)
+        .map_err(Box::<dyn Error>::from)
+        .and_then(|_| recorder.save(&path));
+    match result {
+        Ok(()) => println!("replay saved to {}", path.display()),
+        Err(err) => println!("could not save replay: {}", err),
+    }
+}
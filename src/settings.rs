@@ -0,0 +1,211 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::{Kicks180, RandomizerKind, RotationSystem},
+    master_timing, Fumen, GameMode, Puzzle, Timing,
+};
+
+/// Options picked before a game starts. Read from the command line, e.g.
+/// `tetrust --seed 42 --randomizer tgm --rotation ars --das 8 --arr 0` or
+/// `tetrust --fumen v115@...`.
+#[derive(Resource, Default, Debug)]
+pub struct Settings {
+    /// Seed for the piece randomizer. A new random seed is used for every game when unset.
+    pub seed: Option<u64>,
+    pub ruleset: Ruleset,
+    /// Replay file to play back instead of a live game, given with `--replay`.
+    pub replay: Option<PathBuf>,
+    /// Puzzle picked for [`GameMode::Puzzle`].
+    pub puzzle: Option<Puzzle>,
+    /// Board, hold and queue an endless game starts from, given with `--fumen` or imported from
+    /// the clipboard.
+    pub fumen: Option<Fumen>,
+    /// `fumen` was imported, so it sets up only the game it was imported for.
+    pub fumen_imported: bool,
+}
+
+/// The settings that change how a game plays out. Replays store them so they play back under the
+/// same rules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ruleset {
+    pub mode: GameMode,
+    pub randomizer: RandomizerKind,
+    pub rotation: RotationSystem,
+    pub kicks_180: Kicks180,
+    /// A rotate key held as a piece spawns turns it right away.
+    pub irs: bool,
+    /// The hold key held as a piece spawns puts it straight into hold.
+    pub ihs: bool,
+    /// Ticks an arrow has to be held before the piece starts sliding.
+    pub das: u32,
+    /// Ticks between shifts while sliding. 0 slides straight to the wall.
+    pub arr: u32,
+    /// Ticks auto shift waits after a rotation or hold.
+    pub das_cut: u32,
+    pub lock_delay: LockDelayMode,
+    /// Ticks a piece can rest on something before it locks.
+    pub lock_delay_frames: u32,
+    /// Moves and rotations that can put off locking in [`LockDelayMode::MoveReset`].
+    pub max_lock_resets: u32,
+}
+
+/// What puts off locking while a piece rests on something. Reaching a new lowest row always
+/// restarts the delay.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockDelayMode {
+    /// Every move or rotation restarts the delay.
+    Infinite,
+    /// Moves and rotations restart the delay up to `max_lock_resets` times, after which the piece
+    /// locks as soon as it touches down.
+    #[default]
+    MoveReset,
+    /// Only falling restarts the delay.
+    StepReset,
+    /// Pieces lock the moment they touch down.
+    Instant,
+}
+
+impl LockDelayMode {
+    pub fn from_name(name: &str) -> Option<LockDelayMode> {
+        match name {
+            "infinite" => Some(LockDelayMode::Infinite),
+            "move" => Some(LockDelayMode::MoveReset),
+            "step" => Some(LockDelayMode::StepReset),
+            "none" => Some(LockDelayMode::Instant),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Ruleset {
+            mode: GameMode::default(),
+            randomizer: RandomizerKind::default(),
+            rotation: RotationSystem::default(),
+            kicks_180: Kicks180::default(),
+            irs: true,
+            ihs: true,
+            das: 10,
+            arr: 2,
+            das_cut: 1,
+            lock_delay: LockDelayMode::default(),
+            lock_delay_frames: 30,
+            max_lock_resets: 15,
+        }
+    }
+}
+
+impl Ruleset {
+    /// Timing a game starts with. Only [`GameMode::Master`] changes it as the game goes on.
+    pub fn timing(&self) -> Timing {
+        match self.mode {
+            GameMode::Master => master_timing(0),
+            _ => Timing {
+                are: 0,
+                line_clear: 30,
+                lock_delay: self.lock_delay_frames,
+            },
+        }
+    }
+}
+
+impl Settings {
+    /// The puzzle to set up, if a puzzle is being played.
+    pub fn current_puzzle(&self) -> Option<&Puzzle> {
+        match self.ruleset.mode {
+            GameMode::Puzzle { .. } => self.puzzle.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The fumen board to practise on, if an endless game is being played.
+    pub fn current_fumen(&self) -> Option<&Fumen> {
+        match self.ruleset.mode {
+            GameMode::Endless => self.fumen.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn from_args() -> Settings {
+        let mut settings = Settings::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--seed" {
+                settings.seed = args.next().and_then(|seed| seed.parse().ok());
+            }
+            if arg == "--randomizer" {
+                settings.ruleset.randomizer = args
+                    .next()
+                    .and_then(|name| RandomizerKind::from_name(&name))
+                    .unwrap_or_default();
+            }
+            if arg == "--rotation" {
+                settings.ruleset.rotation = args
+                    .next()
+                    .and_then(|name| RotationSystem::from_name(&name))
+                    .unwrap_or_default();
+            }
+            if arg == "--kicks180" {
+                settings.ruleset.kicks_180 = args
+                    .next()
+                    .and_then(|name| Kicks180::from_name(&name))
+                    .unwrap_or_default();
+            }
+            if arg == "--no-irs" {
+                settings.ruleset.irs = false;
+            }
+            if arg == "--no-ihs" {
+                settings.ruleset.ihs = false;
+            }
+            if arg == "--das" {
+                settings.ruleset.das = parse_or(args.next(), Ruleset::default().das);
+            }
+            if arg == "--arr" {
+                settings.ruleset.arr = parse_or(args.next(), Ruleset::default().arr);
+            }
+            if arg == "--das-cut" {
+                settings.ruleset.das_cut = parse_or(args.next(), Ruleset::default().das_cut);
+            }
+            if arg == "--lock-delay" {
+                settings.ruleset.lock_delay = args
+                    .next()
+                    .and_then(|name| LockDelayMode::from_name(&name))
+                    .unwrap_or_default();
+            }
+            if arg == "--lock-delay-frames" {
+                settings.ruleset.lock_delay_frames =
+                    parse_or(args.next(), Ruleset::default().lock_delay_frames);
+            }
+            if arg == "--lock-resets" {
+                settings.ruleset.max_lock_resets =
+                    parse_or(args.next(), Ruleset::default().max_lock_resets);
+            }
+            if arg == "--replay" {
+                settings.replay = args.next().map(PathBuf::from);
+            }
+            if arg == "--fumen" {
+                settings.fumen = args.next().and_then(|fumen| {
+                    Fumen::decode(&fumen)
+                        .map_err(|err| println!("could not load fumen: {}", err))
+                        .ok()
+                });
+            }
+        }
+
+        settings
+    }
+
+    pub fn game_seed(&self) -> u64 {
+        self.seed.unwrap_or_else(|| rand::thread_rng().gen())
+    }
+}
+
+fn parse_or<T: std::str::FromStr>(arg: Option<String>, default: T) -> T {
+    arg.and_then(|arg| arg.parse().ok()).unwrap_or(default)
+}
@@ -5,10 +5,28 @@ use bevy::{
 };
 
 use crate::{
-    ClearEvent, GameOverEvent, HoldPieceEvent, LevelUpEvent, MoveEvent, PiecePlacedEvent,
-    RotateEvent,
+    Action, ClearEvent, GameOverEvent, GameState, HoldPieceEvent, KeyBindings, LevelUpEvent,
+    MoveEvent, PiecePlacedEvent, RotateEvent, TSpinEvent,
 };
 
+/// Music and sound effects, left out when built without the `audio` feature.
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, sound_setup)
+            .add_systems(OnEnter(GameState::Paused), internal_pause_music)
+            .add_systems(OnExit(GameState::Paused), internal_pause_music)
+            .add_systems(
+                Update,
+                (
+                    pause_music.run_if(not(in_state(GameState::Controls))),
+                    sound_effects,
+                ),
+            );
+    }
+}
+
 #[derive(Component)]
 pub struct GameMusic;
 
@@ -40,6 +58,7 @@ pub fn sound_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
 // TODO: pause on game over and pause
 pub fn pause_music(
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
     music_controller: Query<&AudioSink, With<GameMusic>>,
     mut ev_window: EventReader<WindowFocused>,
 ) {
@@ -55,7 +74,7 @@ pub fn pause_music(
         }
     }
 
-    if keyboard_input.just_pressed(KeyCode::KeyM) {
+    if bindings.just_pressed(&keyboard_input, Action::MuteMusic) {
         internal_pause_music(music_controller);
     }
 }
@@ -66,6 +85,7 @@ pub fn internal_pause_music(music_controller: Query<&AudioSink, With<GameMusic>>
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn sound_effects(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
@@ -76,7 +96,21 @@ pub fn sound_effects(
     mut ev_game_over: EventReader<GameOverEvent>,
     mut ev_level_up: EventReader<LevelUpEvent>,
     mut ev_hold: EventReader<HoldPieceEvent>,
+    mut ev_t_spin: EventReader<TSpinEvent>,
 ) {
+    // spins get the big clear sound no matter how many rows they take, none included
+    let mut spun = false;
+    for _ in ev_t_spin.read() {
+        spun = true;
+        commands.spawn(AudioBundle {
+            source: asset_server.load("sounds/tetris.mp3"),
+            settings: PlaybackSettings {
+                mode: PlaybackMode::Despawn,
+                ..default()
+            },
+        });
+    }
+
     for _ in ev_placed.read() {
         commands.spawn(AudioBundle {
             source: asset_server.load("sounds/drop.mp3"),
@@ -88,8 +122,17 @@ pub fn sound_effects(
     }
 
     for ev in ev_clear.read() {
-        println!("lines cleared: {:?}", ev.0);
-        if ev.0.len() == 4 {
+        if ev.perfect {
+            commands.spawn(AudioBundle {
+                source: asset_server.load("sounds/perfect_clear.mp3"),
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Despawn,
+                    ..default()
+                },
+            });
+        } else if spun {
+            // already sounded as a spin
+        } else if ev.rows.len() == 4 {
             commands.spawn(AudioBundle {
                 source: asset_server.load("sounds/tetris.mp3"),
                 settings: PlaybackSettings {
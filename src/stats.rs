@@ -1,131 +1,287 @@
-use std::time::Duration;
-
 use bevy::prelude::*;
 
-use crate::{get_row, Active, DropTimer, PiecePlacedEvent};
+use crate::{
+    engine::{Spin, VISIBLE_HEIGHT},
+    placed_row, Engine, Gravity, PiecePlacedEvent, Settings, Timing,
+};
 
-#[derive(Component)]
+#[derive(Resource, Default, Debug)]
 pub struct Score(pub u32);
 
-#[derive(Component)]
+#[derive(Resource, Debug)]
 pub struct Level(pub u32);
 
+/// Perfect clears made this game.
+#[derive(Resource, Default, Debug)]
+pub struct PerfectClears(pub u32);
+
+impl Default for Level {
+    fn default() -> Self {
+        Level(1)
+    }
+}
+
+/// Text showing the [`Score`].
+#[derive(Component)]
+pub struct ScoreText;
+
+/// Text showing the [`Level`].
+#[derive(Component)]
+pub struct LevelText;
+
+/// Text showing a running back-to-back chain and combo, and the [`PerfectClears`] so far.
+#[derive(Component)]
+pub struct StreakText;
+
+/// Rows filled by the piece that just locked.
 #[derive(Event)]
-pub struct ClearEvent(pub Vec<usize>);
+pub struct ClearEvent {
+    /// Rows of `PlacedPieces`, counted from the top.
+    pub rows: Vec<usize>,
+    pub spin: Option<Spin>,
+    pub back_to_back: bool,
+    /// Clears in a row before this one.
+    pub combo: u32,
+    /// The clear emptied the board.
+    pub perfect: bool,
+    /// Rows among `rows` with garbage in them.
+    pub garbage: usize,
+}
 
 #[derive(Event)]
 pub struct LevelUpEvent;
 
-fn get_score(rows_cleared: u32) -> u32 {
-    match rows_cleared {
-        1 => 100,
-        2 => 300,
-        3 => 500,
-        4 => 800,
-        _ => 0, // never hapens
+/// A piece locked as a T-spin, clearing `lines` rows.
+#[derive(Event)]
+pub struct TSpinEvent {
+    pub spin: Spin,
+    pub lines: usize,
+}
+
+fn get_score(rows_cleared: u32, spin: Option<Spin>) -> u32 {
+    match (spin, rows_cleared) {
+        (None, 1) => 100,
+        (None, 2) => 300,
+        (None, 3) => 500,
+        (None, 4) => 800,
+        (Some(Spin::Mini), 0) => 100,
+        (Some(Spin::Mini), 1) => 200,
+        (Some(Spin::Mini), 2) => 400,
+        (Some(Spin::Full), 0) => 400,
+        (Some(Spin::Full), 1) => 800,
+        (Some(Spin::Full), 2) => 1200,
+        (Some(Spin::Full), 3) => 1600,
+        _ => 0,
+    }
+}
+
+/// Bonus on top of the regular clear score when the clear empties the board.
+fn get_perfect_clear_bonus(rows_cleared: u32, back_to_back: bool) -> u32 {
+    match (rows_cleared, back_to_back) {
+        (1, _) => 800,
+        (2, _) => 1200,
+        (3, _) => 1800,
+        (4, false) => 2000,
+        (4, true) => 3200,
+        _ => 0,
     }
 }
 
 pub fn score(
-    piece_query: Query<&Children, With<Active>>,
-    child_query: Query<&GlobalTransform, With<Active>>,
-    level: Query<&Level>,
-    mut score: Query<(&mut Score, &mut Text)>,
-    mut ev_clear: EventReader<ClearEvent>,
+    engine: Res<Engine>,
+    level: Res<Level>,
+    mut score: ResMut<Score>,
+    mut perfect_clears: ResMut<PerfectClears>,
     mut ev_piece_placed: EventReader<PiecePlacedEvent>,
     mut ev_restart: EventReader<crate::RestartGameEvent>,
 ) {
     if ev_restart.read().next().is_some() {
-        for (mut score, mut text) in score.iter_mut() {
-            score.0 = 0;
-            text.sections[0].value = score.0.to_string();
-        }
+        *score = Score::default();
+        *perfect_clears = PerfectClears::default();
         return;
     }
 
     let mut score_to_add = 0;
-    for ev in ev_piece_placed.read() {
-        let children = if let Ok(some) = piece_query.get(ev.0) {
-            some
+    for _ in ev_piece_placed.read() {
+        let piece = if let Some(piece) = engine.last_locked {
+            piece
         } else {
             return;
         };
-        let min_row = children
-            .iter()
-            .map(|&child| {
-                let child_global_transform = if let Ok(some) = child_query.get(child) {
-                    some
-                } else {
-                    return 0;
-                };
-                let child_global_translation = child_global_transform.translation();
-                get_row(child_global_translation.y)
-            })
-            .min()
-            .unwrap_or(0);
+        // row of the highest cell, counted from the top of the grid
+        let top = piece.cells().iter().map(|&(_, y)| y).max().unwrap_or(0);
+        let min_row = placed_row(top.clamp(0, VISIBLE_HEIGHT as i32 - 1));
 
         let min_row = if min_row > 0 { min_row - 1 } else { min_row };
 
-        score_to_add += 1 * min_row as u32;
-    }
+        score_to_add += min_row as u32;
 
-    let level = level.iter().next().unwrap();
-    for ev in ev_clear.read() {
-        score_to_add += get_score(ev.0.len() as u32) * level.0;
+        // the engine already knows which rows this piece fills
+        let clear = if let Some(clear) = &engine.last_clear {
+            clear
+        } else {
+            score_to_add += get_score(0, engine.last_spin) * level.0;
+            continue;
+        };
+        let mut clear_score = get_score(clear.rows.len() as u32, clear.spin) * level.0;
+        if clear.back_to_back {
+            clear_score = clear_score * 3 / 2;
+        }
+        score_to_add += clear_score + 50 * clear.combo * level.0;
+        if clear.perfect {
+            perfect_clears.0 += 1;
+            score_to_add +=
+                get_perfect_clear_bonus(clear.rows.len() as u32, clear.back_to_back) * level.0;
+        }
     }
 
-    for (mut score, mut text) in score.iter_mut() {
+    if score_to_add > 0 {
         score.0 += score_to_add;
-        text.sections[0].value = score.0.to_string();
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn level(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut drop_timer: ResMut<DropTimer>,
-    mut level_query: Query<(&mut Level, &mut Text)>,
+    settings: Res<Settings>,
+    mut gravity: ResMut<Gravity>,
+    mut timing: ResMut<Timing>,
+    mut level: ResMut<Level>,
     mut ev_clear: EventReader<ClearEvent>,
     mut lines_cleared: Local<u32>,
     mut ev_level_up: EventWriter<LevelUpEvent>,
     mut ev_restart: EventReader<crate::RestartGameEvent>,
 ) {
+    let mode = settings.ruleset.mode;
     if ev_restart.read().next().is_some() {
-        for (mut level, mut text) in level_query.iter_mut() {
-            level.0 = 0;
-            text.sections[0].value = level.0.to_string();
-        }
+        *level = Level(mode.start_level());
+        *lines_cleared = 0;
+        *gravity = Gravity::new(mode.gravity(level.0));
+        *timing = settings.ruleset.timing();
+        return;
+    }
+    // other modes keep the speed they started with
+    if !mode.levels_up() {
+        ev_clear.clear();
         return;
     }
 
-    let should_increase_level = keyboard_input.just_pressed(KeyCode::KeyL);
     for ev in ev_clear.read() {
-        *lines_cleared += ev.0.len() as u32;
+        *lines_cleared += ev.rows.len() as u32;
     }
-    if *lines_cleared < 10 && !should_increase_level {
+    if *lines_cleared < 10 {
         return;
     }
 
-    *lines_cleared = *lines_cleared % 10;
+    *lines_cleared %= 10;
     // increase level
 
-    let mut cur_level = 0;
-    for (mut level, mut text) in level_query.iter_mut() {
-        level.0 += 1;
-        text.sections[0].value = level.0.to_string();
-        cur_level = level.0;
-    }
+    level.0 += 1;
     ev_level_up.send(LevelUpEvent);
 
     // increase game speed
-    let prev_duration = drop_timer.0.duration();
-    if cur_level <= 10 {
-        let new_duration = prev_duration - Duration::from_millis(60);
-        drop_timer.0.set_duration(new_duration);
+    gravity.set(mode.gravity(level.0));
+}
+
+/// Pop-up text naming the last special clear. Cleared again when the timer runs out.
+#[derive(Component)]
+pub struct ClearLabel(pub Timer);
+
+fn t_spin_name(spin: Spin, lines: usize) -> String {
+    let name = match spin {
+        Spin::Mini => "MINI T-SPIN",
+        Spin::Full => "T-SPIN",
+    };
+    match lines {
+        1 => format!("{} SINGLE", name),
+        2 => format!("{} DOUBLE", name),
+        3 => format!("{} TRIPLE", name),
+        _ => name.to_string(),
+    }
+}
+
+fn clear_name(ev: &ClearEvent) -> String {
+    let mut name = match ev.spin {
+        Some(spin) => t_spin_name(spin, ev.rows.len()),
+        None if ev.rows.len() == 4 => "TETRIS".to_string(),
+        None => String::new(),
+    };
+    if ev.back_to_back {
+        name = format!("B2B {}", name);
+    }
+    if ev.combo > 0 {
+        if !name.is_empty() {
+            name.push('\n');
+        }
+        name += &format!("{} COMBO", ev.combo);
+    }
+    if ev.perfect {
+        name = format!("PERFECT CLEAR\n{}", name);
+    }
+    name
+}
+
+pub fn show_clear_label(
+    time: Res<Time>,
+    mut ev_t_spin: EventReader<TSpinEvent>,
+    mut ev_clear: EventReader<ClearEvent>,
+    mut label: Query<(&mut ClearLabel, &mut Text)>,
+) {
+    let (mut label, mut text) = if let Ok(label) = label.get_single_mut() {
+        label
+    } else {
         return;
+    };
+
+    for ev in ev_t_spin.read() {
+        text.sections[0].value = t_spin_name(ev.spin, ev.lines);
+        label.0.reset();
     }
+    for ev in ev_clear.read() {
+        let name = clear_name(ev);
+        if !name.is_empty() {
+            text.sections[0].value = name;
+            label.0.reset();
+        }
+    }
+
+    if label.0.tick(time.delta()).just_finished() {
+        text.sections[0].value.clear();
+    }
+}
 
-    if cur_level == 13 || cur_level == 16 || cur_level == 19 || cur_level == 29 {
-        let new_duration = prev_duration - Duration::from_millis(60);
-        drop_timer.0.set_duration(new_duration);
+pub fn update_stats_text(
+    engine: Res<Engine>,
+    score: Res<Score>,
+    level: Res<Level>,
+    perfect_clears: Res<PerfectClears>,
+    mut score_text: Query<&mut Text, (With<ScoreText>, Without<LevelText>)>,
+    mut level_text: Query<&mut Text, (With<LevelText>, Without<StreakText>)>,
+    mut streak_text: Query<&mut Text, (With<StreakText>, Without<ScoreText>)>,
+) {
+    if score.is_changed() {
+        for mut text in score_text.iter_mut() {
+            text.sections[0].value = score.0.to_string();
+        }
+    }
+    if level.is_changed() {
+        for mut text in level_text.iter_mut() {
+            text.sections[0].value = level.0.to_string();
+        }
+    }
+    if engine.is_changed() || perfect_clears.is_changed() {
+        let mut streak = Vec::new();
+        if engine.back_to_back {
+            streak.push("B2B".to_string());
+        }
+        match engine.combo {
+            Some(combo) if combo > 0 => streak.push(format!("COMBO {}", combo)),
+            _ => {}
+        }
+        if perfect_clears.0 > 0 {
+            streak.push(format!("PC {}", perfect_clears.0));
+        }
+        for mut text in streak_text.iter_mut() {
+            text.sections[0].value = streak.join("  ");
+        }
     }
 }
@@ -0,0 +1,711 @@
+//! Whole games run headless: no window, audio or rendering, only [`SimulationPlugin`] driven by a
+//! scripted replay, one simulation tick per update.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+
+use crate::{
+    engine::{Cell, Piece, PieceKind, RotationState, BOARD_WIDTH},
+    restart_game, title_button, Action, ClearEvent, DigProgress, Engine, Fumen, GameMode,
+    GameOverEvent, GameOverMenu, GameState, GoalReachedEvent, HoldPieceEvent, MasterProgress,
+    PerfectClears, PiecePlacedEvent, PlacedPieces, PlayerInput, PuzzleGoal, PuzzleProgress,
+    Puzzles, Records, Replay, ReplayPlayback, ReplayRecorder, RestartGameEvent, Ruleset, Score,
+    Settings, SimulationPlugin, SprintProgress, SurvivalProgress, TitleButton, TitleMenu,
+    UltraProgress,
+};
+
+const SEED: u64 = 7;
+
+fn idle(ticks: u32) -> (u32, PlayerInput) {
+    (ticks, PlayerInput::default())
+}
+
+fn tap(action: Action) -> (u32, PlayerInput) {
+    let mut input = PlayerInput::default();
+    input.press(action);
+    (1, input)
+}
+
+/// Holds `action` down for `ticks` without pressing it anew.
+fn held(action: Action, ticks: u32) -> (u32, PlayerInput) {
+    let mut input = PlayerInput::default();
+    input.hold(action);
+    (ticks, input)
+}
+
+fn headless_app(script: Vec<(u32, PlayerInput)>) -> App {
+    headless_app_with_rules(Ruleset::default(), script)
+}
+
+fn headless_app_with_rules(ruleset: Ruleset, script: Vec<(u32, PlayerInput)>) -> App {
+    headless_app_with_settings(
+        Settings {
+            seed: Some(SEED),
+            ruleset,
+            ..default()
+        },
+        script,
+    )
+}
+
+fn headless_app_with_settings(settings: Settings, script: Vec<(u32, PlayerInput)>) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, HierarchyPlugin))
+        .init_resource::<ButtonInput<KeyCode>>()
+        .insert_state(GameState::Replay)
+        .insert_resource(settings)
+        .insert_resource(ReplayPlayback::new(Replay {
+            seed: SEED,
+            inputs: script,
+            ..default()
+        }))
+        .add_plugins(SimulationPlugin)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1.0 / 60.0,
+        )));
+
+    // startup
+    app.update();
+    app
+}
+
+/// Runs `ticks` updates and returns how many `E` events were sent meanwhile.
+fn step<E: Event>(app: &mut App, ticks: u32) -> usize {
+    let mut reader = app.world.resource::<Events<E>>().get_reader_current();
+    let mut count = 0;
+    for _ in 0..ticks {
+        app.update();
+        count += reader.read(app.world.resource::<Events<E>>()).count();
+    }
+    count
+}
+
+fn placed_sprites(app: &App) -> usize {
+    app.world
+        .resource::<PlacedPieces>()
+        .0
+        .iter()
+        .flatten()
+        .filter(|cell| cell.is_some())
+        .count()
+}
+
+/// Shifts the active piece `cells` to the right and hard drops it.
+fn drop_right(cells: u32) -> Vec<(u32, PlayerInput)> {
+    let mut script = vec![];
+    for _ in 0..cells {
+        script.push(tap(Action::MoveRight));
+        script.push(idle(1));
+    }
+    script.push(tap(Action::HardDrop));
+    script.push(idle(5));
+    script
+}
+
+/// Waits for the first piece to spawn and hard drops it.
+fn one_hard_drop() -> Vec<(u32, PlayerInput)> {
+    vec![idle(10), tap(Action::HardDrop), idle(600)]
+}
+
+/// Fills the bottom `rows` but for the columns 4 to 7, where the active piece, made a flat I,
+/// drops in.
+fn set_up_i_gap(app: &mut App, rows: i32) {
+    let mut engine = app.world.resource_mut::<Engine>();
+    for y in 0..rows {
+        for x in 0..BOARD_WIDTH as i32 {
+            let cell = (!(4..8).contains(&x)).then_some(PieceKind::Square.into());
+            engine.board.set(x, y, cell);
+        }
+    }
+    engine.active = Some(Piece::new(PieceKind::Straight));
+}
+
+fn drop_until_game_over() -> Vec<(u32, PlayerInput)> {
+    let mut script = vec![idle(10)];
+    for _ in 0..60 {
+        script.push(tap(Action::HardDrop));
+        script.push(idle(6));
+    }
+    script
+}
+
+#[test]
+fn hard_drop_locks_piece_on_floor() {
+    let mut app = headless_app(one_hard_drop());
+
+    assert_eq!(step::<PiecePlacedEvent>(&mut app, 15), 1);
+
+    assert_eq!(placed_sprites(&app), 4);
+    assert!(app.world.resource::<PlacedPieces>().0[19]
+        .iter()
+        .any(|cell| cell.is_some()));
+    let board = &app.world.resource::<Engine>().board;
+    assert!((0..BOARD_WIDTH as i32).any(|x| board.get(x, 0).is_some()));
+    assert!(app.world.resource::<Score>().0 > 0);
+}
+
+#[test]
+fn filling_a_row_clears_it() {
+    let mut app = headless_app(one_hard_drop());
+
+    // a flat I piece spawns over columns 4 to 7, leave exactly that gap open
+    set_up_i_gap(&mut app, 1);
+
+    assert_eq!(step::<ClearEvent>(&mut app, 15), 1);
+    // let the clear animation finish
+    step::<ClearEvent>(&mut app, 40);
+
+    let engine = app.world.resource::<Engine>();
+    assert!(engine.board.is_empty());
+    assert!(engine.active.is_some());
+    assert_eq!(placed_sprites(&app), 0);
+    assert!(app.world.resource::<Score>().0 >= 100);
+}
+
+#[test]
+fn emptying_the_board_is_a_perfect_clear() {
+    let mut app = headless_app(one_hard_drop());
+
+    // only the bottom row fills, the one above is left over
+    set_up_i_gap(&mut app, 2);
+    step::<ClearEvent>(&mut app, 15);
+    assert_eq!(app.world.resource::<PerfectClears>().0, 0);
+
+    let mut app = headless_app(one_hard_drop());
+    set_up_i_gap(&mut app, 1);
+    step::<ClearEvent>(&mut app, 15);
+
+    assert!(
+        app.world
+            .resource::<Engine>()
+            .last_clear
+            .as_ref()
+            .unwrap()
+            .perfect
+    );
+    assert_eq!(app.world.resource::<PerfectClears>().0, 1);
+    // single plus the perfect clear single bonus
+    assert!(app.world.resource::<Score>().0 >= 900);
+}
+
+#[test]
+fn sprint_ends_at_the_line_target() {
+    let ruleset = Ruleset {
+        mode: GameMode::Sprint { lines: 1 },
+        ..default()
+    };
+    let mut app = headless_app_with_rules(ruleset, one_hard_drop());
+
+    set_up_i_gap(&mut app, 1);
+
+    assert_eq!(step::<GoalReachedEvent>(&mut app, 15), 1);
+    step::<GoalReachedEvent>(&mut app, 2);
+    assert_eq!(
+        app.world.resource::<State<GameState>>().get(),
+        &GameState::GameOver
+    );
+    let progress = app.world.resource::<SprintProgress>();
+    assert!(progress.finished);
+    assert_eq!(progress.lines, 1);
+    // replays never set records
+    assert!(app.world.resource::<Records>().sprint.is_empty());
+}
+
+#[test]
+fn ultra_ends_when_time_is_up() {
+    let ruleset = Ruleset {
+        mode: GameMode::Ultra { seconds: 1 },
+        ..default()
+    };
+    let mut app = headless_app_with_rules(ruleset, vec![idle(600)]);
+
+    assert_eq!(step::<GoalReachedEvent>(&mut app, 59), 0);
+    assert_eq!(step::<GoalReachedEvent>(&mut app, 2), 1);
+    step::<GoalReachedEvent>(&mut app, 1);
+    assert_eq!(
+        app.world.resource::<State<GameState>>().get(),
+        &GameState::GameOver
+    );
+    assert!(app.world.resource::<UltraProgress>().finished);
+}
+
+#[test]
+fn master_pieces_wait_out_the_entry_delay_and_land_at_once() {
+    let ruleset = Ruleset {
+        mode: GameMode::Master,
+        ..default()
+    };
+    let mut app = headless_app_with_rules(ruleset, one_hard_drop());
+
+    assert_eq!(step::<PiecePlacedEvent>(&mut app, 11), 1);
+    assert_eq!(app.world.resource::<MasterProgress>().level, 1);
+    // 18 ticks of entry delay in the first section
+    step::<PiecePlacedEvent>(&mut app, 17);
+    assert!(app.world.resource::<Engine>().active.is_none());
+    step::<PiecePlacedEvent>(&mut app, 2);
+
+    let engine = app.world.resource::<Engine>();
+    let active = engine.active.unwrap();
+    assert!(!engine.board.fits(&active.shifted(0, -1)));
+}
+
+#[test]
+fn dig_ends_when_the_garbage_is_dug_out() {
+    let ruleset = Ruleset {
+        mode: GameMode::Dig {
+            lines: 1,
+            messiness: 0,
+        },
+        ..default()
+    };
+    let mut app = headless_app_with_rules(ruleset, one_hard_drop());
+
+    step::<GoalReachedEvent>(&mut app, 1);
+    assert_eq!(app.world.resource::<Engine>().board.garbage_rows(), 1);
+    assert_eq!(placed_sprites(&app), BOARD_WIDTH - 1);
+
+    // move the hole to where a flat I piece drops
+    set_up_i_gap(&mut app, 1);
+    let mut engine = app.world.resource_mut::<Engine>();
+    for x in (0..4).chain(8..BOARD_WIDTH as i32) {
+        engine.board.set(x, 0, Some(Cell::Garbage));
+    }
+
+    assert_eq!(step::<GoalReachedEvent>(&mut app, 15), 1);
+    let progress = app.world.resource::<DigProgress>();
+    assert_eq!((progress.given, progress.dug), (1, 1));
+}
+
+#[test]
+fn survival_garbage_rises_under_the_stack() {
+    let ruleset = Ruleset {
+        mode: GameMode::Survival,
+        ..default()
+    };
+    let mut app = headless_app_with_rules(ruleset, one_hard_drop());
+
+    step::<PiecePlacedEvent>(&mut app, 20);
+    assert_eq!(placed_sprites(&app), 4);
+    let locked = app.world.resource::<Engine>().last_locked.unwrap();
+
+    // the first row rises after 8 seconds
+    step::<PiecePlacedEvent>(&mut app, 458);
+    assert_eq!(app.world.resource::<SurvivalProgress>().rows, 0);
+    step::<PiecePlacedEvent>(&mut app, 2);
+    assert_eq!(app.world.resource::<SurvivalProgress>().rows, 1);
+
+    let engine = app.world.resource::<Engine>();
+    assert_eq!(engine.board.garbage_rows(), 1);
+    for (x, y) in locked.cells() {
+        assert!(engine.board.get(x, y + 1).is_some());
+    }
+    assert_eq!(placed_sprites(&app), 4 + BOARD_WIDTH - 1);
+}
+
+#[test]
+fn puzzles_are_solved_or_failed_with_their_queue() {
+    let puzzle = Puzzles::load()
+        .0
+        .into_iter()
+        .find(|puzzle| puzzle.goal == PuzzleGoal::PerfectClear)
+        .unwrap();
+    let play = |script| {
+        headless_app_with_settings(
+            Settings {
+                seed: Some(SEED),
+                ruleset: Ruleset {
+                    mode: GameMode::Puzzle { index: 0, count: 1 },
+                    ..default()
+                },
+                puzzle: Some(puzzle.clone()),
+                ..default()
+            },
+            script,
+        )
+    };
+
+    let mut solved = play([vec![idle(5)], drop_right(2), drop_right(4)].concat());
+    assert_eq!(placed_sprites(&solved), 12);
+    assert_eq!(step::<GoalReachedEvent>(&mut solved, 40), 1);
+    assert!(solved.world.resource::<PuzzleProgress>().solved);
+
+    let mut failed = play([vec![idle(5)], drop_right(0), drop_right(0)].concat());
+    assert_eq!(step::<GameOverEvent>(&mut failed, 40), 1);
+    let progress = failed.world.resource::<PuzzleProgress>();
+    assert!(progress.failed && !progress.solved);
+}
+
+#[test]
+fn fumen_practice_starts_on_its_board_and_queue() {
+    let fumen = Fumen::decode("v115@9gF8DeF8DeF8DeF8NeAgH").unwrap();
+    let app = headless_app_with_settings(
+        Settings {
+            seed: Some(SEED),
+            fumen: Some(Fumen {
+                hold: Some(PieceKind::Straight),
+                queue: vec![PieceKind::T, PieceKind::Square],
+                ..fumen
+            }),
+            ..default()
+        },
+        vec![idle(5)],
+    );
+
+    let engine = app.world.resource::<Engine>();
+    assert_eq!(engine.board.get(5, 3), Some(Cell::Garbage));
+    assert_eq!(engine.active.map(|piece| piece.kind), Some(PieceKind::T));
+    assert_eq!(engine.next.front(), Some(&PieceKind::Square));
+    assert_eq!(engine.hold, Some(PieceKind::Straight));
+    assert_eq!(placed_sprites(&app), 24);
+}
+
+#[test]
+fn holding_a_shift_key_slides_the_piece_to_the_wall() {
+    let mut app = headless_app(vec![
+        idle(10),
+        tap(Action::MoveRight),
+        held(Action::MoveRight, 40),
+        idle(600),
+    ]);
+    app.world.resource_mut::<Engine>().active = Some(Piece::new(PieceKind::Straight));
+
+    // one cell on the press, then nothing until the auto shift charges
+    step::<PiecePlacedEvent>(&mut app, 11);
+    let right = |app: &App| {
+        let active = app.world.resource::<Engine>().active.unwrap();
+        active.cells().iter().map(|&(x, _)| x).max().unwrap()
+    };
+    assert_eq!(right(&app), 8);
+    step::<PiecePlacedEvent>(&mut app, 5);
+    assert_eq!(right(&app), 8);
+
+    step::<PiecePlacedEvent>(&mut app, 30);
+    assert_eq!(right(&app), BOARD_WIDTH as i32 - 1);
+}
+
+#[test]
+fn rotate_keys_turn_the_piece() {
+    let mut app = headless_app(vec![
+        idle(10),
+        tap(Action::RotateClockwise),
+        idle(1),
+        tap(Action::Rotate180),
+        idle(1),
+        tap(Action::RotateCounterClockwise),
+        idle(600),
+    ]);
+    app.world.resource_mut::<Engine>().active = Some(Piece::new(PieceKind::T));
+
+    let mut rotations = vec![];
+    for ticks in [11, 2, 2] {
+        step::<PiecePlacedEvent>(&mut app, ticks);
+        rotations.push(app.world.resource::<Engine>().active.unwrap().rotation);
+    }
+    assert_eq!(
+        rotations,
+        [RotationState::R, RotationState::L, RotationState::Two]
+    );
+}
+
+#[test]
+fn keys_pressed_on_the_same_tick_all_apply() {
+    let mut together = PlayerInput::default();
+    for action in [Action::RotateClockwise, Action::MoveRight, Action::HardDrop] {
+        together.press(action);
+    }
+    let mut app = headless_app(vec![idle(10), (1, together), idle(600)]);
+    let piece = Piece::new(PieceKind::T);
+    app.world.resource_mut::<Engine>().active = Some(piece);
+
+    assert_eq!(step::<PiecePlacedEvent>(&mut app, 12), 1);
+    let locked = app.world.resource::<Engine>().last_locked.unwrap();
+    assert_eq!(locked.rotation, RotationState::R);
+    assert_eq!(locked.x, piece.x + 1);
+}
+
+#[test]
+fn soft_dropped_pieces_lock_after_the_lock_delay() {
+    let mut app = headless_app(vec![
+        idle(10),
+        tap(Action::SoftDrop),
+        held(Action::SoftDrop, 600),
+    ]);
+
+    // a row every 6 ticks from the spawn row down to the floor
+    assert_eq!(step::<PiecePlacedEvent>(&mut app, 120), 0);
+    let engine = app.world.resource::<Engine>();
+    let active = engine.active.unwrap();
+    assert!(!engine.board.fits(&active.shifted(0, -1)));
+
+    assert_eq!(step::<PiecePlacedEvent>(&mut app, 40), 1);
+    assert_eq!(placed_sprites(&app), 4);
+}
+
+/// Puts `rows`, drawn as for [`crate::engine::Board::drawn`], under a T at `(x, y)` turned to
+/// `rotation`, which the script then turns into place and hard drops.
+fn set_up_t_spin(app: &mut App, rows: &[&str], (rotation, x, y): (RotationState, i32, i32)) {
+    let mut engine = app.world.resource_mut::<Engine>();
+    engine.board = crate::engine::Board::drawn(rows);
+    engine.active = Some(Piece {
+        rotation,
+        x,
+        y,
+        ..Piece::new(PieceKind::T)
+    });
+}
+
+#[test]
+fn t_spins_score_their_own_points() {
+    let turn_and_drop = |action| {
+        vec![
+            idle(10),
+            tap(action),
+            idle(1),
+            tap(Action::HardDrop),
+            idle(60),
+        ]
+    };
+
+    // a T-spin double, 1200 points, resting on the floor 17 rows from the top
+    let mut app = headless_app(turn_and_drop(Action::RotateClockwise));
+    set_up_t_spin(
+        &mut app,
+        &["...#......", "###...####", "####.#####"],
+        (RotationState::R, 4, 1),
+    );
+    assert_eq!(step::<ClearEvent>(&mut app, 15), 1);
+    assert_eq!(app.world.resource::<Score>().0, 17 + 1200);
+
+    // a mini T-spin single made a full one by the last kick, 800 points
+    let mut app = headless_app(turn_and_drop(Action::RotateClockwise));
+    set_up_t_spin(
+        &mut app,
+        &[
+            ".#........",
+            "..........",
+            "#.#.......",
+            "#..#######",
+            "#..#######",
+        ],
+        (RotationState::Zero, 2, 3),
+    );
+    assert_eq!(step::<ClearEvent>(&mut app, 15), 1);
+    assert_eq!(app.world.resource::<Score>().0, 16 + 800);
+
+    // a mini T-spin single, 200 points
+    let mut app = headless_app(turn_and_drop(Action::RotateClockwise));
+    set_up_t_spin(
+        &mut app,
+        &["#..#......", "...#######"],
+        (RotationState::L, 2, 1),
+    );
+    assert_eq!(step::<ClearEvent>(&mut app, 15), 1);
+    assert_eq!(app.world.resource::<Score>().0, 17 + 200);
+}
+
+#[test]
+fn back_to_back_and_combo_clears_score_extra() {
+    let mut script = vec![idle(10)];
+    for _ in 0..4 {
+        script.extend([tap(Action::HardDrop), idle(59)]);
+    }
+    let mut app = headless_app(script);
+    // fills the bottom `rows` but for the last column, where an upright I drops in, and leaves a
+    // cell on top so the board never empties
+    let mut drop_i_into_well = |rows: i32| {
+        let mut engine = app.world.resource_mut::<Engine>();
+        engine.board = crate::engine::Board::default();
+        for y in 0..rows {
+            for x in 0..BOARD_WIDTH as i32 - 1 {
+                engine.board.set(x, y, Some(Cell::Garbage));
+            }
+        }
+        engine.board.set(0, rows, Some(Cell::Garbage));
+        engine.active = Some(Piece {
+            rotation: RotationState::R,
+            x: BOARD_WIDTH as i32 - 2,
+            y: 10,
+            ..Piece::new(PieceKind::Straight)
+        });
+        // it drops within the next 60 ticks, and the piece after it comes in before they are up
+        assert_eq!(step::<ClearEvent>(&mut app, 60), 1);
+        app.world.resource::<Score>().0
+    };
+
+    // every I lands on the floor, 15 rows from the top, the first making a tetris
+    assert_eq!(drop_i_into_well(4), 15 + 800);
+    // a back-to-back tetris, half as much again, and the first combo
+    assert_eq!(drop_i_into_well(4), 815 + 15 + 1200 + 50);
+    // a single breaks the back-to-back chain but not the combo
+    assert_eq!(drop_i_into_well(1), 2080 + 15 + 100 + 2 * 50);
+    // so the next tetris is not back-to-back
+    assert_eq!(drop_i_into_well(4), 2295 + 15 + 800 + 3 * 50);
+}
+
+/// Lets [`RestartGameEvent`] start a new game, as the menus do outside headless tests.
+fn add_restarts(app: &mut App) {
+    app.add_systems(Update, restart_game);
+    app.world.spawn((GameOverMenu, Visibility::Hidden));
+}
+
+#[test]
+fn an_imported_fumen_sets_up_only_the_game_it_was_imported_for() {
+    let fumen = Fumen::decode("v115@9gF8DeF8DeF8DeF8NeAgH").unwrap();
+    let mut app = headless_app(vec![idle(600)]);
+    add_restarts(&mut app);
+    let mut settings = app.world.resource_mut::<Settings>();
+    settings.fumen = Some(fumen.clone());
+    settings.fumen_imported = true;
+
+    app.world.send_event(RestartGameEvent);
+    app.update();
+    assert!(!app.world.resource::<Engine>().board.is_empty());
+    // the replay still knows what the game started from
+    assert_eq!(
+        app.world.resource::<ReplayRecorder>().fumen,
+        Some(fumen.encode())
+    );
+
+    app.world.send_event(RestartGameEvent);
+    app.update();
+    assert!(app.world.resource::<Engine>().board.is_empty());
+    assert_eq!(placed_sprites(&app), 0);
+    assert_eq!(app.world.resource::<ReplayRecorder>().fumen, None);
+}
+
+#[test]
+fn games_started_from_the_title_menu_start_on_an_empty_board() {
+    let fumen = Fumen::decode("v115@9gF8DeF8DeF8DeF8NeAgH").unwrap();
+    let mut app = headless_app_with_settings(
+        Settings {
+            seed: Some(SEED),
+            fumen: Some(fumen),
+            ..default()
+        },
+        vec![idle(600)],
+    );
+    assert!(!app.world.resource::<Engine>().board.is_empty());
+
+    add_restarts(&mut app);
+    app.add_systems(Update, title_button.before(restart_game));
+    app.world.spawn((TitleMenu, Visibility::Visible));
+    app.world
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::Title);
+    app.update();
+    app.world
+        .spawn((Interaction::Pressed, TitleButton(GameMode::Endless)));
+    app.update();
+
+    assert!(app.world.resource::<Engine>().board.is_empty());
+    assert_eq!(placed_sprites(&app), 0);
+    assert!(app.world.resource::<Settings>().fumen.is_none());
+}
+
+#[test]
+fn hold_swaps_once_per_piece() {
+    let mut app = headless_app(vec![
+        idle(10),
+        tap(Action::Hold),
+        idle(1),
+        tap(Action::Hold),
+        idle(600),
+    ]);
+    let engine = app.world.resource::<Engine>();
+    let first = engine.active.unwrap().kind;
+    let second = engine.next[0];
+
+    assert_eq!(step::<HoldPieceEvent>(&mut app, 11), 1);
+    let engine = app.world.resource::<Engine>();
+    assert_eq!(engine.hold, Some(first));
+    assert_eq!(engine.active.unwrap().kind, second);
+
+    // holding again before the piece locks does nothing
+    assert_eq!(step::<HoldPieceEvent>(&mut app, 2), 0);
+    let engine = app.world.resource::<Engine>();
+    assert_eq!(engine.hold, Some(first));
+    assert_eq!(engine.active.unwrap().kind, second);
+}
+
+#[test]
+fn rotate_held_through_the_entry_delay_turns_the_next_piece() {
+    let script = vec![
+        idle(10),
+        tap(Action::HardDrop),
+        held(Action::RotateClockwise, 30),
+        idle(600),
+    ];
+    for irs in [true, false] {
+        let ruleset = Ruleset {
+            mode: GameMode::Master,
+            irs,
+            ..default()
+        };
+        let mut app = headless_app_with_rules(ruleset, script.clone());
+        let next = app.world.resource::<Engine>().next[0];
+
+        step::<PiecePlacedEvent>(&mut app, 30);
+        let active = app.world.resource::<Engine>().active.unwrap();
+        assert_eq!(active.kind, next);
+        let expected = if irs {
+            RotationState::R
+        } else {
+            RotationState::Zero
+        };
+        assert_eq!(active.rotation, expected, "irs: {}", irs);
+    }
+}
+
+#[test]
+fn hold_held_through_the_entry_delay_holds_the_next_piece() {
+    let ruleset = Ruleset {
+        mode: GameMode::Master,
+        ..default()
+    };
+    let mut app = headless_app_with_rules(
+        ruleset,
+        vec![
+            idle(10),
+            tap(Action::HardDrop),
+            held(Action::Hold, 30),
+            idle(600),
+        ],
+    );
+    let engine = app.world.resource::<Engine>();
+    let (second, third) = (engine.next[0], engine.next[1]);
+
+    assert_eq!(step::<HoldPieceEvent>(&mut app, 30), 1);
+    let engine = app.world.resource::<Engine>();
+    assert_eq!(engine.hold, Some(second));
+    assert_eq!(engine.active.unwrap().kind, third);
+}
+
+#[test]
+fn stacking_to_the_top_ends_the_game() {
+    let mut app = headless_app(drop_until_game_over());
+
+    assert_eq!(step::<GameOverEvent>(&mut app, 400), 1);
+    assert_eq!(
+        app.world.resource::<State<GameState>>().get(),
+        &GameState::GameOver
+    );
+    assert!(app.world.resource::<Engine>().is_game_over());
+}
+
+#[test]
+fn replays_are_deterministic() {
+    let mut first = headless_app(drop_until_game_over());
+    let mut second = headless_app(drop_until_game_over());
+    step::<GameOverEvent>(&mut first, 400);
+    step::<GameOverEvent>(&mut second, 400);
+
+    assert_eq!(
+        first.world.resource::<Engine>().board,
+        second.world.resource::<Engine>().board
+    );
+    assert_eq!(
+        first.world.resource::<Score>().0,
+        second.world.resource::<Score>().0
+    );
+}
@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-use crate::{toggle_menu, GameMusic, GameState, RestartGameEvent, TitleMenu};
+use crate::{GameMode, Puzzles, RestartGameEvent, Settings, TitleMenu};
 
 pub const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
@@ -9,10 +9,13 @@ const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
 #[derive(Component)]
 pub struct PauseButton;
 
+/// Buttons the pointer has just moved onto, off or pressed.
+type ChangedButtons = (Changed<Interaction>, With<Button>);
+
 pub fn button_system(
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor, &mut BorderColor),
-        (Changed<Interaction>, With<Button>),
+        ChangedButtons,
     >,
 ) {
     for (interaction, mut color, mut border_color) in &mut interaction_query {
@@ -62,26 +65,62 @@ pub fn restart_button(
     }
 }
 
+/// Starts a game in the given mode from the title menu.
 #[derive(Component)]
-pub struct TitleButton;
+pub struct TitleButton(pub GameMode);
 
 pub fn title_button(
-    title_button: Query<&Interaction, (Changed<Interaction>, With<TitleButton>)>,
-    game_state: Res<State<GameState>>,
-    mut next_state: ResMut<NextState<GameState>>,
-    music_controller: Query<&AudioSink, With<GameMusic>>,
+    title_button: Query<(&Interaction, &TitleButton), Changed<Interaction>>,
+    puzzles: Res<Puzzles>,
+    mut settings: ResMut<Settings>,
     mut query: Query<&mut Visibility, With<TitleMenu>>,
+    mut ev_restart: EventWriter<RestartGameEvent>,
 ) {
-    for interaction in &mut title_button.iter() {
+    for (interaction, button) in &mut title_button.iter() {
         if *interaction == Interaction::Pressed {
-            toggle_menu(
-                game_state,
-                &mut next_state,
-                music_controller,
-                &mut query.single_mut(),
-            );
+            // the game set up at startup may not suit the mode, so start over
+            settings.ruleset.mode = button.0;
+            settings.puzzle = match button.0 {
+                GameMode::Puzzle { index, .. } => puzzles.0.get(index).cloned(),
+                _ => None,
+            };
+            // a board set up in the editor or imported was for practice, not for every game after
+            settings.fumen = None;
+            *query.single_mut() = Visibility::Hidden;
+            ev_restart.send(RestartGameEvent);
 
             return;
         }
     }
 }
+
+/// Cycles an option of the [`TitleButton`] it points to, such as the line target of a sprint.
+#[derive(Component)]
+pub struct ModeOptionButton {
+    pub mode_button: Entity,
+    pub option: usize,
+}
+
+pub fn mode_option_button(
+    option_button: Query<(&Interaction, &ModeOptionButton, &Children), Changed<Interaction>>,
+    mut title_buttons: Query<&mut TitleButton>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, option, children) in &mut option_button.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let mut button = if let Ok(button) = title_buttons.get_mut(option.mode_button) {
+            button
+        } else {
+            continue;
+        };
+        button.0.cycle_option(option.option);
+        if let (Ok(mut text), Some(label)) = (
+            text_query.get_mut(children[0]),
+            button.0.option_labels().get(option.option),
+        ) {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
@@ -1,209 +1,87 @@
-use std::collections::HashSet;
-
 use crate::{
-    get_col, get_row, internal_pause_music, Active, Collision, CollisionEvent, GameMusic,
-    GameState, Ghost, PauseGameEvent, PiecePlacedEvent, PieceType, PlacedPieces, RestartGameEvent,
-    BOTTOM_GRID, LEFT_GRID, RIGHT_GRID, SQUARE_SIZE,
+    engine::{Input, BOARD_WIDTH},
+    fall_instantly,
+    frame_timer::FrameTimer,
+    Action, Active, Engine, GameState, Gravity, HoldPieceEvent, KeyBindings, LockDelayMode,
+    PauseGameEvent, PiecePlacedEvent, PlayerInput, RestartGameEvent, Ruleset, Settings, Timing,
 };
 use bevy::prelude::*;
 
 #[derive(Resource)]
-pub struct MovementTimer(pub Timer);
-
-#[derive(Resource)]
-pub struct UserDropTimer(pub Timer);
-
-#[derive(Component, Default, Debug)]
-pub enum RotationState {
-    #[default]
-    Zero, // zero rotates
-    R,   // clockwise or "right" rotation
-    L,   // counter-clockwise or "left" rotation
-    Two, // two rotations starting from zero
+pub struct SoftDropTimer(pub FrameTimer);
+
+/// Delayed auto shift for the left and right arrows. A press moves once, holding charges for
+/// `das` ticks and then repeats every `arr` ticks, or straight to the wall when `arr` is 0.
+#[derive(Resource, Default)]
+pub struct AutoShift {
+    direction: Option<Action>,
+    charge: u32,
+    repeat: u32,
+    /// Ticks left, counting the current one, in which auto shift is held off.
+    cut: u32,
 }
 
-fn get_rotation_state(rotation_state: &RotationState, is_clockwise: bool) -> RotationState {
-    if is_clockwise {
-        match rotation_state {
-            RotationState::Zero => RotationState::R,
-            RotationState::R => RotationState::Two,
-            RotationState::Two => RotationState::L,
-            RotationState::L => RotationState::Zero,
+impl AutoShift {
+    /// Updates the held direction for this tick and returns how many cells to shift towards it.
+    /// Charging carries on while there is no active piece, so a charged shift applies as soon as
+    /// the next piece spawns.
+    pub fn shifts(&mut self, input: &PlayerInput, ruleset: &Ruleset) -> u32 {
+        let directions = [Action::MoveLeft, Action::MoveRight];
+        let newest = directions.into_iter().find(|&a| input.just_pressed(a));
+        let direction = newest
+            .or_else(|| self.direction.filter(|&a| input.pressed(a)))
+            .or_else(|| directions.into_iter().find(|&a| input.pressed(a)));
+        let cut = self.cut > 0;
+        self.cut = self.cut.saturating_sub(1);
+
+        if newest.is_some() || direction != self.direction {
+            self.direction = direction;
+            self.charge = 0;
+            self.repeat = 0;
+            self.cut = 0;
+            return direction.is_some() as u32;
         }
-    } else {
-        match rotation_state {
-            RotationState::Zero => RotationState::L,
-            RotationState::L => RotationState::Two,
-            RotationState::Two => RotationState::R,
-            RotationState::R => RotationState::Zero,
+        if direction.is_none() {
+            return 0;
         }
-    }
-}
-
-const KICK_VALUES: [[(i32, i32); 5]; 8] = [
-    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 0->R
-    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R-> 0
-    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R-> 2
-    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 2->R
-    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 2-> L
-    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L->2
-    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L-> 0
-    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 0->L
-];
-
-const I_KICK_VALUES: [[(i32, i32); 5]; 8] = [
-    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // 0->R
-    [(0, 0), (2, 0), (-1, 0), (1, 2), (-2, -1)], // R-> 0
-    [(0, 0), (-1, 0), (2, 0), (1, 2), (2, -1)],  // R->2
-    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // 2->R
-    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // 2-> L
-    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // L->2
-    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // L-> 0
-    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // 0->L
-];
-
-fn get_kick_values(
-    current_state: &RotationState,
-    next_state: &RotationState,
-    piece_type: &PieceType,
-    placed_pieces: &PlacedPieces,
-    child_translations: Vec<Vec3>,
-) -> Option<(i32, i32)> {
-    // check if piece is colliding
-    let index = match (current_state, next_state) {
-        (RotationState::Zero, RotationState::R) => 0,
-        (RotationState::R, RotationState::Zero) => 1,
-        (RotationState::R, RotationState::Two) => 2,
-        (RotationState::Two, RotationState::R) => 3,
-        (RotationState::Two, RotationState::L) => 4,
-        (RotationState::L, RotationState::Two) => 5,
-        (RotationState::L, RotationState::Zero) => 6,
-        (RotationState::Zero, RotationState::L) => 7,
-        _ => panic!("impossible rotation transition"),
-    };
-    let kick_values = match piece_type {
-        PieceType::Straight => I_KICK_VALUES[index],
-        _ => KICK_VALUES[index],
-    };
 
-    println!("current state: {:?}", current_state);
-    println!("next state: {:?}", next_state);
-
-    for &kick in kick_values.iter() {
-        let mut new_child_translations = vec![];
-        for translation in child_translations.iter() {
-            println!("original translation {:?}", translation);
-            new_child_translations.push(Vec3::new(
-                translation.x + (kick.0 as f32) * SQUARE_SIZE,
-                translation.y + (kick.1 as f32) * SQUARE_SIZE,
-                translation.z,
-            ));
-        }
-
-        let mut can_kick = true;
-        for translation in new_child_translations.iter() {
-            println!("checking translation {:?}", translation);
-            println!("kick {:?}", kick);
-            if !is_valid_position(translation, placed_pieces) {
-                can_kick = false;
-                break;
+        if self.charge < ruleset.das {
+            self.charge += 1;
+            if self.charge < ruleset.das {
+                return 0;
+            }
+        } else {
+            self.repeat += 1;
+            if self.repeat < ruleset.arr {
+                return 0;
             }
         }
+        self.repeat = 0;
 
-        if can_kick {
-            return Some(kick);
+        if cut {
+            return 0;
         }
-    }
-
-    None
-}
-
-pub fn is_valid_positions(child_translations: &Vec<Vec3>, placed_pieces: &PlacedPieces) -> bool {
-    for translation in child_translations {
-        if !is_valid_position(translation, placed_pieces) {
-            return false;
+        if ruleset.arr == 0 {
+            BOARD_WIDTH as u32
+        } else {
+            1
         }
     }
-    return true;
-}
 
-fn is_valid_position(translation: &Vec3, placed_pieces: &PlacedPieces) -> bool {
-    if translation.x < LEFT_GRID
-        || translation.x > RIGHT_GRID - SQUARE_SIZE
-        || translation.y < BOTTOM_GRID
-    {
-        //println!("wall collision");
-        return false;
+    pub fn direction(&self) -> Option<Action> {
+        self.direction
     }
 
-    let row = get_row(translation.y);
-    let col = get_col(translation.x);
-    if placed_pieces.0[row][col].is_some() {
-        //println!("piece collision");
-        return false;
-    }
-    //println!("valid position");
-    true
-}
-
-fn get_rotate_translation(translation: Vec3, is_clockwise: bool) -> Vec3 {
-    match is_clockwise {
-        true => Vec3::new(translation.y, -translation.x, translation.z),
-        false => Vec3::new(-translation.y, translation.x, translation.z),
+    /// Holds off auto shift for `ticks` ticks, starting with the current one, after a rotation or
+    /// hold, so a charged shift doesn't carry the new orientation or piece away by accident. The
+    /// charge keeps building meanwhile, and pressing a direction anew moves at once.
+    pub fn cut(&mut self, ticks: u32) {
+        self.cut = ticks;
     }
 }
 
-fn rotate_clockwise(
-    children: &Children,
-    is_clockwise: bool,
-    rotation_state: &mut RotationState,
-    placed_pieces: Res<PlacedPieces>,
-    piece_type: &PieceType,
-    parent_translation: &Vec3,
-    child_query: &mut Query<(&GlobalTransform, &mut Transform), Without<Children>>,
-    translation: &mut Vec3,
-) -> bool {
-    let next_state = get_rotation_state(rotation_state, is_clockwise);
-    let mut translations_to_apply: Vec<Vec3> = vec![];
-    let mut global_translations: Vec<Vec3> = vec![];
-    for &child in children.iter() {
-        let child_transform = child_query.get(child).unwrap();
-        let child_translation = child_transform.1.translation;
-        let child_global_translation = child_transform.0.translation();
-        println!("child translation {:?}", child_global_translation);
-
-        let new_translation = get_rotate_translation(child_translation, is_clockwise);
-        let new_global_translation = *parent_translation + new_translation;
-        //println!("new translation {:?}", new_translation);
-        //println!("new global translation {:?}", new_global_translation);
-        translations_to_apply.push(new_translation);
-        global_translations.push(new_global_translation);
-    }
-
-    let kick_values = if let Some(values) = get_kick_values(
-        rotation_state,
-        &next_state,
-        &piece_type,
-        &placed_pieces,
-        global_translations,
-    ) {
-        values
-    } else {
-        return false;
-    };
-
-    for (i, &child) in children.iter().enumerate() {
-        let mut child_transform = child_query.get_mut(child).unwrap();
-        child_transform.1.translation = translations_to_apply[i];
-    }
-
-    translation.x += (kick_values.0 as f32) * SQUARE_SIZE;
-    translation.y += (kick_values.1 as f32) * SQUARE_SIZE;
-
-    *rotation_state = next_state;
-
-    true
-}
+#[derive(Resource)]
+pub struct UserDropTimer(pub FrameTimer);
 
 #[derive(Component)]
 pub struct Hold;
@@ -215,57 +93,30 @@ pub struct MoveEvent;
 pub struct RotateEvent;
 
 pub fn user_rotate_active(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    placed_pieces: Res<PlacedPieces>,
-    mut query: Query<
-        (
-            &Children,
-            &mut Transform,
-            &PieceType,
-            &mut RotationState,
-            &GlobalTransform,
-        ),
-        With<Active>,
-    >,
-    mut child_query: Query<(&GlobalTransform, &mut Transform), Without<Children>>,
+    input: Res<PlayerInput>,
+    gravity: Res<Gravity>,
+    mut engine: ResMut<Engine>,
     mut ev_rotate: EventWriter<RotateEvent>,
 ) {
-    let (children, mut transform, piece_type, mut rotation_state, global_transform) =
-        if let Ok(piece) = query.get_single_mut() {
-            piece
-        } else {
-            return;
-        };
     //rotate left
-    if keyboard_input.just_pressed(KeyCode::KeyZ) {
-        if rotate_clockwise(
-            children,
-            /* is_clockwise=*/ true,
-            &mut rotation_state,
-            placed_pieces,
-            piece_type,
-            &global_transform.translation(),
-            &mut child_query,
-            &mut transform.translation,
-        ) {
+    if input.just_pressed(Action::RotateClockwise) {
+        if !engine.step(Input::RotateClockwise).is_empty() {
+            fall_instantly(&mut engine, &gravity);
             ev_rotate.send(RotateEvent);
         }
         return;
     }
     //rotate right
-    if keyboard_input.just_pressed(KeyCode::KeyX) {
-        if rotate_clockwise(
-            children,
-            /* is_clockwise=*/ false,
-            &mut rotation_state,
-            placed_pieces,
-            piece_type,
-            &global_transform.translation(),
-            &mut child_query,
-            &mut transform.translation,
-        ) {
+    if input.just_pressed(Action::RotateCounterClockwise) {
+        if !engine.step(Input::RotateCounterClockwise).is_empty() {
+            fall_instantly(&mut engine, &gravity);
             ev_rotate.send(RotateEvent);
         }
+        return;
+    }
+    if input.just_pressed(Action::Rotate180) && !engine.step(Input::Rotate180).is_empty() {
+        fall_instantly(&mut engine, &gravity);
+        ev_rotate.send(RotateEvent);
     }
 }
 
@@ -275,25 +126,94 @@ pub struct AttemptPlaceEvent(pub Entity);
 #[derive(Event)]
 pub struct DropPieceEvent(pub Entity);
 
-#[derive(Resource)]
-pub struct GracePeriodTimer(pub Timer);
+/// Lock delay of the active piece: how long it has rested on something and how many times a move
+/// or rotation has put that off.
+#[derive(Resource, Default)]
+pub struct LockDelay {
+    elapsed: u32,
+    resets: u32,
+    lowest_y: Option<i32>,
+}
+
+impl LockDelay {
+    /// Counts one more tick on the ground. Returns whether the piece should lock now.
+    fn tick(&mut self, ruleset: &Ruleset, timing: &Timing) -> bool {
+        let out_of_resets = ruleset.lock_delay == LockDelayMode::MoveReset
+            && self.resets >= ruleset.max_lock_resets;
+        if ruleset.lock_delay == LockDelayMode::Instant || out_of_resets {
+            return true;
+        }
+
+        self.elapsed += 1;
+        self.elapsed >= timing.lock_delay
+    }
+
+    /// Called after a successful move or rotation.
+    fn moved(&mut self, ruleset: &Ruleset) {
+        match ruleset.lock_delay {
+            LockDelayMode::Infinite => self.elapsed = 0,
+            LockDelayMode::MoveReset if self.resets < ruleset.max_lock_resets => {
+                self.elapsed = 0;
+                self.resets += 1;
+            }
+            _ => {}
+        }
+    }
+
+    /// Reaching a new lowest row restarts the delay and the reset count in every mode.
+    fn fell_to(&mut self, y: i32) {
+        if self.lowest_y.is_none_or(|lowest_y| y < lowest_y) {
+            *self = LockDelay {
+                lowest_y: Some(y),
+                ..default()
+            };
+        }
+    }
+}
 
 #[derive(Component)]
 pub struct AttemptingPlace;
 
+#[allow(clippy::too_many_arguments)]
 pub fn try_to_place_piece(
-    time: Res<Time>,
+    settings: Res<Settings>,
+    timing: Res<Timing>,
     mut commands: Commands,
+    mut engine: ResMut<Engine>,
+    mut lock_delay: ResMut<LockDelay>,
     mut ev_drop_piece: EventReader<DropPieceEvent>,
     mut ev_attempt_place: EventReader<AttemptPlaceEvent>,
+    mut ev_move: EventReader<MoveEvent>,
+    mut ev_rotate: EventReader<RotateEvent>,
+    mut ev_hold: EventReader<HoldPieceEvent>,
     mut ev_piece_placed: EventWriter<PiecePlacedEvent>,
-    mut grace_period_timer: ResMut<GracePeriodTimer>,
     mut query: Query<Entity, (With<Active>, With<Children>)>,
 ) {
+    let ruleset = &settings.ruleset;
+    let moved = ev_move.read().count() > 0 || ev_rotate.read().count() > 0;
+
+    // hard dropped pieces are already locked by the engine
+    if let Some(ev) = ev_drop_piece.read().next() {
+        ev_piece_placed.send(PiecePlacedEvent(ev.0));
+        *lock_delay = LockDelay::default();
+        ev_attempt_place.clear();
+        return;
+    }
+    // a piece from hold starts over
+    if ev_hold.read().count() > 0 {
+        *lock_delay = LockDelay::default();
+    }
+
+    if let Some(active) = engine.active {
+        lock_delay.fell_to(active.y);
+    }
+    if moved {
+        lock_delay.moved(ruleset);
+    }
+
     let ev = if let Some(events) = ev_attempt_place.read().next() {
         events
     } else {
-        grace_period_timer.0.reset();
         let entity = if let Ok(piece) = query.get_single_mut() {
             piece
         } else {
@@ -303,122 +223,83 @@ pub fn try_to_place_piece(
         return;
     };
 
-    let mut piece = if let Ok(piece) = query.get(ev.0) {
+    let piece = if let Ok(piece) = query.get(ev.0) {
         piece
     } else {
         return;
     };
 
-    // if piece is not found, it means it has either been placed already or has been deleted
-    // due to overlap from game ending
-    if commands.get_entity(piece).is_none() {
-        return;
-    }
-
     commands.entity(piece).insert(AttemptingPlace);
-    if !grace_period_timer.0.tick(time.delta()).just_finished() && ev_drop_piece.is_empty()
-    //    && !keyboard_input.pressed(KeyCode::ArrowDown) // makes it on instant drop if down is
-    //    pressed
-    {
+    if !lock_delay.tick(ruleset, &timing) {
         ev_attempt_place.clear();
         return;
     }
 
-    if !ev_drop_piece.is_empty() {
-        piece = ev_drop_piece.read().next().unwrap().0;
-    }
-
+    engine.step(Input::Lock);
     ev_piece_placed.send(PiecePlacedEvent(piece));
-    println!("piece placed");
 
     commands.entity(piece).remove::<AttemptingPlace>();
-    grace_period_timer.0.reset();
+    *lock_delay = LockDelay::default();
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn user_move_actives(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    child_query: Query<&GlobalTransform, Without<Children>>,
-    time: Res<Time>,
-    ghost_query: Query<&Transform, (With<Ghost>, Without<Active>, With<Children>)>,
+    input: Res<PlayerInput>,
+    settings: Res<Settings>,
+    gravity: Res<Gravity>,
+    query: Query<Entity, (With<Active>, With<Children>)>,
+    mut engine: ResMut<Engine>,
     mut drop_timer: ResMut<UserDropTimer>,
-    mut movement_timer: ResMut<MovementTimer>,
-    mut query: Query<(&Children, &mut Transform, Entity), With<Active>>,
-    mut ev_collision: EventReader<CollisionEvent>,
+    mut soft_drop_timer: ResMut<SoftDropTimer>,
+    mut auto_shift: ResMut<AutoShift>,
     mut ev_move: EventWriter<MoveEvent>,
     mut ev_rotate: EventReader<RotateEvent>,
+    mut ev_hold: EventReader<HoldPieceEvent>,
     mut ev_drop_piece: EventWriter<DropPieceEvent>,
 ) {
-    let can_drop = drop_timer.0.tick(time.delta()).finished();
-    let can_move = movement_timer.0.tick(time.delta()).finished();
-    if ev_rotate.read().count() > 0 {
-        return;
+    let can_drop = drop_timer.0.tick().finished();
+    let can_soft_drop = soft_drop_timer.0.tick().finished();
+    let rotated = ev_rotate.read().count() > 0;
+    if ev_hold.read().count() > 0 || rotated {
+        auto_shift.cut(settings.ruleset.das_cut);
     }
+    let shifts = auto_shift.shifts(&input, &settings.ruleset);
 
-    let mut collisions: HashSet<&Collision> = HashSet::new();
-
-    for collision in ev_collision.read() {
-        collisions.extend(&collision.collision);
-    }
-
-    let (children, mut transform, entity) = if let Ok(piece) = query.get_single_mut() {
+    let entity = if let Ok(piece) = query.get_single() {
         piece
     } else {
         return;
     };
 
-    let mut direction = Vec3::ZERO;
-
-    if keyboard_input.just_pressed(KeyCode::Space) && can_drop {
-        // TODO: change to go down until collide
-        let ghost_transform = if let Ok(ghost) = ghost_query.get_single() {
-            ghost
-        } else {
-            return;
-        };
-        println!("ghost translation {:?}", ghost_transform.translation);
-        transform.translation = ghost_transform.translation;
-        ev_drop_piece.send(DropPieceEvent(entity));
-        println!("dropped piece");
-        drop_timer.0.reset();
-        return;
-    }
-
-    if !can_move {
-        return;
-    }
-
-    if keyboard_input.pressed(KeyCode::ArrowLeft) && !collisions.contains(&Collision::Right) {
-        direction.x -= SQUARE_SIZE;
-        movement_timer.0.reset();
-    }
-    if keyboard_input.pressed(KeyCode::ArrowRight) && !collisions.contains(&Collision::Left) {
-        direction.x += SQUARE_SIZE;
-        movement_timer.0.reset();
+    // a rotation this tick is already applied, then the piece shifts and drops as pressed with it
+    let mut moved = false;
+    let shift = match auto_shift.direction() {
+        Some(Action::MoveLeft) => Input::MoveLeft,
+        _ => Input::MoveRight,
+    };
+    for _ in 0..shifts {
+        if engine.step(shift).is_empty() {
+            break;
+        }
+        fall_instantly(&mut engine, &gravity);
+        moved = true;
     }
-    if keyboard_input.pressed(KeyCode::ArrowDown) && !collisions.contains(&Collision::Top) {
-        direction.y -= SQUARE_SIZE;
-        movement_timer.0.reset();
+    if moved {
+        ev_move.send(MoveEvent);
     }
 
-    for &child in children.iter() {
-        let child_transform = child_query.get(child).unwrap();
-        let child_translation = child_transform.translation();
-        let new_translation = child_translation + direction;
-
-        if new_translation.y < BOTTOM_GRID {
-            direction.y = 0.0;
+    if input.just_pressed(Action::HardDrop) && can_drop {
+        if !engine.step(Input::HardDrop).is_empty() {
+            ev_drop_piece.send(DropPieceEvent(entity));
         }
+        drop_timer.0.reset();
+        return;
     }
 
-    if direction.x != 0.0 {
-        ev_move.send(MoveEvent);
-    }
-    if direction.y != 0.0 && direction.x != 0.0 && collisions.contains(&Collision::Corner) {
-        direction.y = 0.0;
+    if input.pressed(Action::SoftDrop) && can_soft_drop {
+        engine.step(Input::SoftDrop);
+        soft_drop_timer.0.reset();
     }
-
-    transform.translation += direction;
-    //print!("{:?}", transform.translation);
 }
 
 #[derive(Component)]
@@ -427,47 +308,34 @@ pub struct PauseMenu;
 pub fn pause_game(
     game_state: Res<State<GameState>>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    music_controller: Query<&AudioSink, With<GameMusic>>,
+    bindings: Res<KeyBindings>,
     mut pause_menu_query: Query<&mut Visibility, With<PauseMenu>>,
     mut ev_pause: EventReader<PauseGameEvent>,
     mut next_state: ResMut<NextState<GameState>>,
 ) {
     let mut pause_menu = pause_menu_query.single_mut();
     if ev_pause.read().next().is_some() {
-        toggle_menu(
-            game_state,
-            &mut next_state,
-            music_controller,
-            &mut pause_menu,
-        );
+        toggle_menu(game_state, &mut next_state, &mut pause_menu);
         return;
     }
 
-    if keyboard_input.just_pressed(KeyCode::Escape) {
-        toggle_menu(
-            game_state,
-            &mut next_state,
-            music_controller,
-            &mut pause_menu,
-        );
+    if bindings.just_pressed(&keyboard_input, Action::Pause) {
+        toggle_menu(game_state, &mut next_state, &mut pause_menu);
     }
 }
 
 pub fn toggle_menu(
     game_state: Res<State<GameState>>,
     next_state: &mut ResMut<NextState<GameState>>,
-    music_controller: Query<&AudioSink, With<GameMusic>>,
     menu_visibility: &mut Visibility,
 ) {
     match game_state.get() {
         GameState::Paused => {
             next_state.set(GameState::Playing);
-            internal_pause_music(music_controller);
             *menu_visibility = Visibility::Hidden;
         }
         GameState::Playing => {
             next_state.set(GameState::Paused);
-            internal_pause_music(music_controller);
             *menu_visibility = Visibility::Visible;
         }
         GameState::GameOver => {
@@ -478,14 +346,168 @@ pub fn toggle_menu(
             next_state.set(GameState::Playing);
             *menu_visibility = Visibility::Hidden;
         }
+        GameState::Replay => {
+            *menu_visibility = Visibility::Hidden;
+        }
+        GameState::Editor => {
+            next_state.set(GameState::Playing);
+            *menu_visibility = Visibility::Hidden;
+        }
+        GameState::Controls => {
+            *menu_visibility = Visibility::Hidden;
+        }
     }
 }
 
 pub fn user_restart(
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
     mut ev_restart: EventWriter<RestartGameEvent>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::KeyR) {
+    if bindings.just_pressed(&keyboard_input, Action::Restart) {
         ev_restart.send(RestartGameEvent);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Presses `action` on the first tick and holds it for the rest, returning the shifts of
+    /// every tick. `cut_at` cuts auto shift for `das_cut` ticks from that tick on.
+    fn hold_for(ticks: usize, ruleset: &Ruleset, cut_at: Option<usize>) -> Vec<u32> {
+        let mut auto_shift = AutoShift::default();
+        (0..ticks)
+            .map(|tick| {
+                let mut input = PlayerInput::default();
+                if tick == 0 {
+                    input.press(Action::MoveRight);
+                } else {
+                    input.hold(Action::MoveRight);
+                }
+                if cut_at == Some(tick) {
+                    auto_shift.cut(ruleset.das_cut);
+                }
+                auto_shift.shifts(&input, ruleset)
+            })
+            .collect()
+    }
+
+    /// Rests a piece on the ground with a lock delay of 5 ticks, moving it on the ticks in `moves`,
+    /// and returns the tick it locks on, if it does within 100.
+    fn lock_tick(lock_delay: LockDelayMode, moves: &[u32]) -> Option<u32> {
+        let ruleset = Ruleset {
+            lock_delay,
+            max_lock_resets: 2,
+            ..default()
+        };
+        let timing = Timing {
+            lock_delay: 5,
+            ..default()
+        };
+        let mut delay = LockDelay::default();
+        delay.fell_to(0);
+        (1..=100).find(|tick| {
+            if moves.contains(tick) {
+                delay.moved(&ruleset);
+            }
+            delay.tick(&ruleset, &timing)
+        })
+    }
+
+    #[test]
+    fn each_lock_delay_mode_puts_off_locking_its_own_way() {
+        let every_third: Vec<u32> = (1..=100).filter(|tick| tick % 3 == 0).collect();
+
+        assert_eq!(lock_tick(LockDelayMode::Infinite, &[]), Some(5));
+        assert_eq!(lock_tick(LockDelayMode::Infinite, &every_third), None);
+        assert_eq!(lock_tick(LockDelayMode::MoveReset, &[]), Some(5));
+        assert_eq!(lock_tick(LockDelayMode::MoveReset, &[3]), Some(7));
+        assert_eq!(lock_tick(LockDelayMode::StepReset, &[3]), Some(5));
+        assert_eq!(lock_tick(LockDelayMode::Instant, &[]), Some(1));
+    }
+
+    #[test]
+    fn move_reset_locks_at_once_when_out_of_resets() {
+        // the second reset is the last, so the piece locks right away on it
+        assert_eq!(lock_tick(LockDelayMode::MoveReset, &[3, 6]), Some(6));
+        assert_eq!(lock_tick(LockDelayMode::MoveReset, &[2, 3, 4]), Some(3));
+    }
+
+    #[test]
+    fn falling_to_a_new_lowest_row_restores_the_resets() {
+        let ruleset = Ruleset {
+            max_lock_resets: 1,
+            ..default()
+        };
+        let timing = Timing::default();
+        let mut delay = LockDelay::default();
+        delay.fell_to(5);
+        delay.moved(&ruleset);
+        assert!(delay.tick(&ruleset, &timing));
+
+        // the same row or a higher one doesn't
+        delay.fell_to(5);
+        assert!(delay.tick(&ruleset, &timing));
+        delay.fell_to(4);
+        assert!(!delay.tick(&ruleset, &timing));
+    }
+
+    #[test]
+    fn auto_shift_repeats_every_arr_ticks_once_charged() {
+        let ruleset = Ruleset {
+            das: 4,
+            arr: 2,
+            ..default()
+        };
+
+        assert_eq!(hold_for(10, &ruleset, None), [1, 0, 0, 0, 1, 0, 1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn auto_shift_without_arr_goes_straight_to_the_wall() {
+        let ruleset = Ruleset {
+            das: 4,
+            arr: 0,
+            ..default()
+        };
+        let wall = BOARD_WIDTH as u32;
+
+        assert_eq!(hold_for(6, &ruleset, None), [1, 0, 0, 0, wall, wall]);
+    }
+
+    #[test]
+    fn das_cut_holds_auto_shift_off_for_its_ticks() {
+        let ruleset = Ruleset {
+            das: 4,
+            arr: 1,
+            das_cut: 3,
+            ..default()
+        };
+
+        assert_eq!(
+            hold_for(10, &ruleset, Some(5)),
+            [1, 0, 0, 0, 1, 0, 0, 0, 1, 1]
+        );
+        // the charge built up during the cut
+        assert_eq!(
+            hold_for(10, &ruleset, Some(2)),
+            [1, 0, 0, 0, 0, 1, 1, 1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn a_new_press_ends_the_das_cut() {
+        let ruleset = Ruleset {
+            das_cut: 10,
+            ..default()
+        };
+        let mut auto_shift = AutoShift::default();
+        auto_shift.cut(ruleset.das_cut);
+        let mut input = PlayerInput::default();
+        input.press(Action::MoveLeft);
+
+        assert_eq!(auto_shift.shifts(&input, &ruleset), 1);
+        assert_eq!(auto_shift.cut, 0);
+    }
+}